@@ -1,122 +1,716 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
 use std::fs;
-use std::path::Path;
-use std::process::Command;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
 use convert_case::{Case, Casing};
 use itertools::Itertools;
+use serde_json::{Value, json};
+
+/// Set to `write` to also copy the generated bindings to
+/// `src/contract_bindings/generated.rs`, or to `check` to fail the build when
+/// that copy is out of date.
+const BINDINGS_MODE_ENV: &str = "CONTRACT_BINDINGS";
+
+/// Compiler used for the contracts, `forge` (the default) or `solc`.
+const COMPILER_ENV: &str = "CONTRACT_COMPILER";
+
+/// Directory under `OUT_DIR` the contracts are compiled into, so that
+/// `cargo build` never writes to the source tree.
+const ARTIFACTS_DIR: &str = "artifacts";
 
 fn main() {
     println!("cargo:rerun-if-changed=contracts/src");
     println!("cargo:rerun-if-changed=contracts/foundry.toml");
+    println!("cargo:rerun-if-changed=contracts/remappings.txt");
+    println!("cargo:rerun-if-env-changed={}", BINDINGS_MODE_ENV);
+    println!("cargo:rerun-if-env-changed={}", COMPILER_ENV);
 
     let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
     let contracts_dir = Path::new(&manifest_dir).join("contracts");
-    let src_dir = Path::new(&manifest_dir).join("src");
-    let bindings_dir = src_dir.join("contract_bindings");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let checked_in_path = Path::new(&manifest_dir).join("src/contract_bindings/generated.rs");
 
-    // Ensure the contract bindings directory exists
-    if !bindings_dir.exists() {
-        fs::create_dir_all(&bindings_dir).expect("Failed to create contract_bindings directory");
-    }
-
-    // Run forge build to compile contracts
-    compile_contracts(&contracts_dir);
+    // Compile contracts with forge or solc
+    compile_contracts(&contracts_dir, &out_dir);
 
     // Generate contract bindings
-    generate_contract_bindings(&contracts_dir, &bindings_dir);
+    let bindings = generate_contract_bindings(&out_dir);
+
+    // The checked-in copy is only read, never compiled, so the machine
+    // specific artifact paths are abbreviated to keep it stable.
+    let bindings = bindings.replace(&path_string(&out_dir), "$OUT_DIR");
+
+    match env::var(BINDINGS_MODE_ENV).as_deref() {
+        Ok("write") => {
+            if write_if_changed(&checked_in_path, &bindings) {
+                println!(
+                    "cargo:warning=Updated checked-in bindings at: {}",
+                    checked_in_path.display()
+                );
+            }
+        }
+        Ok("check") => {
+            if fs::read_to_string(&checked_in_path).ok().as_ref() != Some(&bindings) {
+                panic!(
+                    "{} is out of date, rebuild with {}=write to update it",
+                    checked_in_path.display(),
+                    BINDINGS_MODE_ENV
+                );
+            }
+        }
+        Ok(mode) => {
+            panic!("unknown {} mode {:?}, expected write or check", BINDINGS_MODE_ENV, mode)
+        }
+        Err(_) => {}
+    }
 }
 
-fn compile_contracts(contracts_dir: &Path) {
+/// Compiles the contracts into [`ARTIFACTS_DIR`] under `out_dir`.
+fn compile_contracts(contracts_dir: &Path, out_dir: &Path) {
     println!("cargo:warning=Compiling Solidity contracts...");
 
-    let output = Command::new("forge")
-        .arg("build")
+    let (compiler, compiled) = match env::var(COMPILER_ENV).as_deref() {
+        Ok("forge") | Err(_) => ("forge", compile_with_forge(contracts_dir, out_dir)),
+        Ok("solc") => ("solc", compile_with_solc(contracts_dir, out_dir)),
+        Ok(compiler) => {
+            panic!("unknown {} {:?}, expected forge or solc", COMPILER_ENV, compiler)
+        }
+    };
+
+    if !compiled {
+        panic!(
+            "{} not found. Install foundry, or install solc and set {}=solc.",
+            compiler, COMPILER_ENV
+        );
+    }
+
+    println!("cargo:warning=Contracts compiled successfully");
+}
+
+/// Runs `forge build` with its output and cache under `out_dir`, returning
+/// `false` if forge is not installed.
+fn compile_with_forge(contracts_dir: &Path, out_dir: &Path) -> bool {
+    let output = match Command::new("forge")
+        .args(["build", "--extra-output", "storageLayout", "--out"])
+        .arg(out_dir.join(ARTIFACTS_DIR))
+        .arg("--cache-path")
+        .arg(out_dir.join("forge-cache"))
         .current_dir(contracts_dir)
         .output()
-        .expect("Failed to execute forge build. Make sure foundry is installed.");
+    {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return false,
+        Err(e) => panic!("Failed to execute forge build: {}", e),
+    };
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
         panic!("forge build failed: {}", stderr);
     }
 
-    println!("cargo:warning=Contracts compiled successfully");
+    true
 }
 
-fn generate_contract_bindings(contracts_dir: &Path, bindings_dir: &Path) {
-    let out_dir = contracts_dir.join("out");
+/// Compiles every source under `contracts/src` with `solc --standard-json`
+/// using the settings in `foundry.toml`, and writes forge-style artifacts to
+/// [`ARTIFACTS_DIR`] under `out_dir`. Returns `false` if solc is not installed.
+fn compile_with_solc(contracts_dir: &Path, out_dir: &Path) -> bool {
+    let config = FoundryConfig::read(contracts_dir);
 
-    if !out_dir.exists() {
-        panic!("Contracts output directory not found. Make sure forge build was successful.");
+    let mut source_paths = Vec::new();
+    find_files(&contracts_dir.join("src"), &mut source_paths);
+    source_paths.sort();
+
+    let sources = source_paths
+        .iter()
+        .filter(|path| path.extension().is_some_and(|extension| extension == "sol"))
+        .map(|path| {
+            let content = fs::read_to_string(path).expect("Failed to read contract source");
+            let relative_path = path
+                .strip_prefix(contracts_dir)
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/");
+            (relative_path, json!({ "content": content }))
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    let mut settings = json!({
+        "optimizer": { "enabled": config.optimizer, "runs": config.optimizer_runs },
+        "remappings": config.remappings,
+        "outputSelection": {
+            "*": {
+                "*": [
+                    "abi",
+                    "metadata",
+                    "evm.bytecode.object",
+                    "evm.deployedBytecode.object",
+                    "storageLayout"
+                ]
+            }
+        },
+    });
+    if let Some(evm_version) = &config.evm_version {
+        settings["evmVersion"] = json!(evm_version);
+    }
+    if config.via_ir {
+        settings["viaIR"] = json!(true);
     }
+    let input = json!({ "language": "Solidity", "sources": sources, "settings": settings });
 
-    let mut contract_modules = Vec::new();
+    println!(
+        "cargo:warning=solc settings: optimizer {}, runs {}, evm version {}, via-ir {}, remappings [{}]",
+        config.optimizer,
+        config.optimizer_runs,
+        config.evm_version.as_deref().unwrap_or("default"),
+        config.via_ir,
+        config.remappings.join(", ")
+    );
 
-    // Find all compiled contract JSON files recursively
-    find_contract_files(&out_dir, &mut contract_modules, contracts_dir);
+    let mut command = Command::new("solc");
+    command.args(["--standard-json", "--base-path", "."]);
+    for lib in config
+        .libs
+        .iter()
+        .filter(|lib| contracts_dir.join(lib).is_dir())
+    {
+        command.args(["--include-path", lib]);
+    }
+
+    let mut child = match command
+        .current_dir(contracts_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return false,
+        Err(e) => panic!("Failed to execute solc: {}", e),
+    };
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.to_string().as_bytes())
+        .expect("Failed to send standard JSON input to solc");
+    let output = child.wait_with_output().expect("Failed to wait for solc");
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        panic!("solc failed: {}", stderr);
+    }
+
+    let output = serde_json::from_slice::<Value>(&output.stdout)
+        .expect("solc returned invalid standard JSON output");
+
+    let errors = output["errors"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|error| error["severity"] == "error")
+        .filter_map(|error| error["formattedMessage"].as_str())
+        .join("\n");
+    if !errors.is_empty() {
+        panic!("solc compilation failed:\n{}", errors);
+    }
 
-    if contract_modules.is_empty() {
-        println!("cargo:warning=No contract files found in {}", out_dir.display());
-        return;
+    // Forge's layout, keyed by the whole source path so that same-named files
+    // in different directories do not overwrite each other.
+    let artifacts_dir = out_dir.join(ARTIFACTS_DIR);
+    let mut written = BTreeSet::new();
+
+    let contracts = output["contracts"].as_object().into_iter().flatten();
+    for (source, contracts) in contracts {
+        let source_dir = artifacts_dir.join(source.strip_prefix("src/").unwrap_or(source));
+
+        for (name, contract) in contracts.as_object().into_iter().flatten() {
+            let bytecode = |key: &str| {
+                let object = contract["evm"][key]["object"].as_str().unwrap_or_default();
+                json!({ "object": format!("0x{}", object) })
+            };
+            // solc returns the metadata as a string, forge stores it parsed
+            let metadata = contract["metadata"]
+                .as_str()
+                .and_then(|metadata| serde_json::from_str::<Value>(metadata).ok())
+                .unwrap_or_default();
+            let artifact = json!({
+                "abi": contract["abi"],
+                "metadata": metadata,
+                "bytecode": bytecode("bytecode"),
+                "deployedBytecode": bytecode("deployedBytecode"),
+                "storageLayout": contract["storageLayout"],
+            });
+
+            let path = source_dir.join(format!("{}.json", name));
+            write_if_changed(&path, &serde_json::to_string_pretty(&artifact).unwrap());
+            written.insert(path);
+        }
     }
 
-    // Generate the main mod.rs file
-    let mod_content = contract_modules
+    remove_stale_artifacts(&artifacts_dir, &written);
+    true
+}
+
+/// Deletes artifacts under `artifacts_dir` left behind by contracts or sources
+/// that no longer exist, along with the directories they leave empty. Only
+/// ever called on the build output in `OUT_DIR`, never on tracked files.
+fn remove_stale_artifacts(artifacts_dir: &Path, written: &BTreeSet<PathBuf>) {
+    let mut existing = Vec::new();
+    find_contract_files(artifacts_dir, &mut existing);
+
+    for path in existing.iter().filter(|path| !written.contains(*path)) {
+        println!("cargo:warning=Removing stale artifact {}", path.display());
+        fs::remove_file(path).expect("Failed to remove stale artifact");
+
+        // Only succeeds once the directory is empty
+        let mut dir = path.parent();
+        while let Some(parent) = dir.filter(|dir| *dir != artifacts_dir) {
+            if fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+    }
+}
+
+/// Compiler settings from the `[profile.default]` section of `foundry.toml`
+/// and `remappings.txt`. Only the flat `key = value` subset of TOML that
+/// foundry configs use is understood.
+struct FoundryConfig {
+    libs: Vec<String>,
+    remappings: Vec<String>,
+    optimizer: bool,
+    optimizer_runs: u64,
+    evm_version: Option<String>,
+    via_ir: bool,
+}
+
+impl FoundryConfig {
+    fn read(contracts_dir: &Path) -> Self {
+        let toml = fs::read_to_string(contracts_dir.join("foundry.toml")).unwrap_or_default();
+        let values = profile_values(&toml, "profile.default");
+        let value = |key: &str| values.get(key).map(String::as_str);
+
+        let mut remappings = value("remappings").map(toml_array).unwrap_or_default();
+        if let Ok(file) = fs::read_to_string(contracts_dir.join("remappings.txt")) {
+            remappings.extend(
+                file.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(String::from),
+            );
+        }
+
+        Self {
+            libs: value("libs")
+                .map(toml_array)
+                .unwrap_or_else(|| vec!["lib".to_string()]),
+            remappings,
+            optimizer: value("optimizer") == Some("true"),
+            optimizer_runs: value("optimizer_runs").map(toml_integer).unwrap_or(200),
+            evm_version: value("evm_version").map(toml_string),
+            via_ir: value("via_ir") == Some("true"),
+        }
+    }
+}
+
+/// Raw values of the keys in `[section]`, with arrays spanning several lines
+/// joined into one.
+fn profile_values(toml: &str, section: &str) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    let mut current_section = String::new();
+    let mut pending: Option<(String, String)> = None;
+
+    for line in toml.lines() {
+        let line = strip_comment(line).trim();
+
+        if let Some((key, mut value)) = pending.take() {
+            value.push_str(line);
+            if line.contains(']') {
+                values.insert(key, value);
+            } else {
+                pending = Some((key, value));
+            }
+            continue;
+        }
+
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            current_section = name.trim().to_string();
+        } else if let Some((key, value)) = line.split_once('=') {
+            if current_section != section {
+                continue;
+            }
+
+            let (key, value) = (key.trim().to_string(), value.trim().to_string());
+            if value.starts_with('[') && !value.contains(']') {
+                pending = Some((key, value));
+            } else {
+                values.insert(key, value);
+            }
+        }
+    }
+
+    values
+}
+
+/// `line` up to its `#` comment, if any. A `#` inside a quoted string is part
+/// of the value.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return &line[..index],
+            (None, '"' | '\'') => quote = Some(c),
+            // Only basic (double quoted) strings have escapes
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), _) if c == open && !escaped => quote = None,
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn toml_string(value: &str) -> String {
+    value
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .to_string()
+}
+
+fn toml_array(value: &str) -> Vec<String> {
+    value
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(toml_string)
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn toml_integer(value: &str) -> u64 {
+    let value = value.trim().replace('_', "");
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .unwrap_or_else(|_| panic!("invalid integer {:?} in foundry.toml", value))
+}
+
+fn find_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// A compiled contract artifact found under the forge output directory.
+struct Artifact {
+    contract_name: String,
+    /// Module path under `contract_bindings`, mirroring the path of the
+    /// source file, e.g. `["gate_lock_sol", "gate_lock"]`.
+    module_path: Vec<String>,
+    /// Path of the source file relative to `contracts`, e.g. `src/GateLock.sol`.
+    source_path: String,
+    /// Absolute path of the artifact JSON, as handed to `sol!`.
+    artifact_path: String,
+    json: Value,
+}
+
+/// Generates `contract_bindings.rs` in `out_dir` from the artifacts compiled
+/// into it, returning its content.
+fn generate_contract_bindings(out_dir: &Path) -> String {
+    let artifacts_dir = out_dir.join(ARTIFACTS_DIR);
+
+    if !artifacts_dir.exists() {
+        panic!("Contracts output directory not found. Make sure the contracts compiled.");
+    }
+
+    // Find all compiled contract JSON files recursively
+    let mut artifact_paths = Vec::new();
+    find_contract_files(&artifacts_dir, &mut artifact_paths);
+    artifact_paths.sort();
+
+    let artifacts = artifact_paths
         .iter()
-        .map(|(_, module_content)| module_content.as_str())
-        .join("\n\n");
-
-    let mod_file_path = bindings_dir.join("mod.rs");
-    fs::write(&mod_file_path, mod_content).expect("Failed to write contract bindings mod.rs");
-
-    println!("cargo:warning=Contract bindings generated at: {}", mod_file_path.display());
-}
-
-fn find_contract_files(
-    dir: &Path,
-    contract_modules: &mut Vec<(String, String)>,
-    contracts_dir: &Path,
-) {
-    if let Ok(entries) = fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-
-            if path.is_dir() {
-                find_contract_files(&path, contract_modules, contracts_dir);
-            } else if let Some(extension) = path.extension() {
-                if extension == "json" {
-                    if let Some(contract_name) = path.file_stem().and_then(|s| s.to_str()) {
-                        // Skip debug files, test files, and build-info files
-                        if contract_name.contains(".dbg")
-                            || path.to_string_lossy().contains(".s.sol")
-                            || path.to_string_lossy().contains("build-info")
-                            || contract_name.chars().all(|c| c.is_ascii_hexdigit())
-                        {
-                            continue;
-                        }
-
-                        // Generate module name (snake_case) and ensure it starts with a letter
-                        let mut module_name = contract_name.to_case(Case::Snake);
-
-                        // If module name starts with a digit, prefix with "contract_"
-                        if module_name
-                            .chars()
-                            .next()
-                            .map_or(false, |c| c.is_ascii_digit())
-                        {
-                            module_name = format!("contract_{}", module_name);
-                        }
-
-                        // Get relative path from project root
-                        let relative_path = path
-                            .strip_prefix(contracts_dir.parent().unwrap())
-                            .unwrap()
-                            .to_string_lossy()
-                            .replace('\\', "/");
-
-                        let module_content = format!(
-                            r#"#[rustfmt::skip]
+        .filter_map(|path| load_artifact(path))
+        .collect::<Vec<_>>();
+
+    // `src/contract_bindings` includes the generated file unconditionally, so
+    // the crate cannot build without it.
+    if artifacts.is_empty() {
+        panic!(
+            "No contract artifacts found in {}. Make sure the contracts compiled.",
+            artifacts_dir.display()
+        );
+    }
+
+    // Skip regeneration when neither the generator nor any ABI or storage
+    // layout changed since the last run. The artifacts' bytecode is picked up
+    // by `sol!` itself.
+    let bindings_path = out_dir.join("contract_bindings.rs");
+    let hash_file_path = out_dir.join("contract_bindings.hash");
+    let generator = format!("build.rs {:016x}", fnv1a(include_str!("build.rs").bytes()));
+    let hashes = [generator]
+        .into_iter()
+        .chain(
+            artifacts
+                .iter()
+                .map(|artifact| format!("{} {:016x}", artifact.artifact_path, abi_hash(artifact))),
+        )
+        .join("\n");
+
+    let cached = fs::read_to_string(&hash_file_path)
+        .is_ok_and(|previous| previous == hashes)
+        .then(|| fs::read_to_string(&bindings_path).ok())
+        .flatten();
+    if let Some(bindings) = cached {
+        println!("cargo:warning=Contract ABIs unchanged, skipping binding generation");
+        return bindings;
+    }
+
+    let mut modules = ModuleTree::default();
+    for artifact in &artifacts {
+        modules.insert(&artifact.module_path, artifact, contract_module(artifact));
+    }
+    let bindings = format!("{}\n", modules.render());
+
+    if write_if_changed(&bindings_path, &bindings) {
+        println!("cargo:warning=Contract bindings generated at: {}", bindings_path.display());
+    } else {
+        println!("cargo:warning=Contract bindings unchanged at: {}", bindings_path.display());
+    }
+
+    fs::write(&hash_file_path, hashes).expect("Failed to write contract ABI hashes");
+    bindings
+}
+
+/// Writes `content` to `path` unless it already holds exactly that, so an
+/// unchanged file keeps its mtime and does not force a recompile. Returns
+/// whether the file was written.
+fn write_if_changed(path: &Path, content: &str) -> bool {
+    if fs::read_to_string(path).is_ok_and(|existing| existing == content) {
+        return false;
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).expect("Failed to create bindings directory");
+    }
+    fs::write(path, content).expect("Failed to write contract bindings");
+    true
+}
+
+fn find_contract_files(dir: &Path, artifact_paths: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            find_contract_files(&path, artifact_paths);
+            continue;
+        }
+
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let Some(contract_name) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+
+        // Skip debug files, test files, and build-info files
+        if contract_name.contains(".dbg")
+            || path.to_string_lossy().contains(".s.sol")
+            || path.to_string_lossy().contains("build-info")
+            || contract_name.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            continue;
+        }
+
+        artifact_paths.push(path);
+    }
+}
+
+fn load_artifact(path: &Path) -> Option<Artifact> {
+    let contract_name = path.file_stem()?.to_str()?.to_string();
+    let artifact_path = path_string(path);
+
+    let json = fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str::<Value>(&json).ok())
+        .unwrap_or_default();
+
+    let source_path = source_path(&json)
+        .unwrap_or_else(|| panic!("{} does not name its source file", artifact_path));
+
+    // One module per directory of the source file below `src` and one for
+    // the file itself (`src/GateLock.sol` -> `gate_lock_sol`), then one for
+    // the contract
+    let module_path = source_path
+        .strip_prefix("src/")
+        .unwrap_or(&source_path)
+        .split('/')
+        .map(module_name)
+        .chain([module_name(&contract_name)])
+        .collect();
+
+    Some(Artifact { contract_name, module_path, source_path, artifact_path, json })
+}
+
+/// Path of the file an artifact was compiled from, relative to `contracts`,
+/// from the compilation target in its metadata (or its AST if present). Unlike
+/// the artifact's place in the output directory this does not depend on the
+/// compiler.
+fn source_path(artifact: &Value) -> Option<String> {
+    artifact["metadata"]["settings"]["compilationTarget"]
+        .as_object()
+        .and_then(|target| target.keys().next().cloned())
+        .or_else(|| artifact["ast"]["absolutePath"].as_str().map(String::from))
+}
+
+/// `path` with forward slashes, as it appears in the generated code.
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Snake case module name for a file, directory or contract name, made to
+/// start with a letter.
+fn module_name(name: &str) -> String {
+    let module_name = name.replace(['.', '-'], "_").to_case(Case::Snake);
+
+    // If module name starts with a digit, prefix with "contract_"
+    if module_name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("contract_{}", module_name)
+    } else {
+        module_name
+    }
+}
+
+/// Generated contract modules nested by module path. Children are kept
+/// sorted so the output does not depend on directory iteration order.
+#[derive(Default)]
+struct ModuleTree {
+    /// Artifact path and bindings of the contract generated at this node.
+    contract: Option<(String, String)>,
+    children: BTreeMap<String, ModuleTree>,
+}
+
+impl ModuleTree {
+    fn insert(&mut self, path: &[String], artifact: &Artifact, module: String) {
+        let conflict = |other: &str| -> ! {
+            panic!(
+                "{} and {} both generate contract_bindings::{}; rename one of the contracts",
+                other,
+                artifact.artifact_path,
+                artifact.module_path.join("::")
+            )
+        };
+
+        if let Some((other, _)) = &self.contract {
+            conflict(other);
+        }
+
+        match path.split_first() {
+            Some((name, rest)) => {
+                self.children
+                    .entry(name.clone())
+                    .or_default()
+                    .insert(rest, artifact, module);
+            }
+            None => {
+                if let Some(other) = self.children.values().find_map(ModuleTree::first_contract) {
+                    conflict(other);
+                }
+                self.contract = Some((artifact.artifact_path.clone(), module));
+            }
+        }
+    }
+
+    fn first_contract(&self) -> Option<&str> {
+        match &self.contract {
+            Some((artifact_path, _)) => Some(artifact_path),
+            None => self.children.values().find_map(ModuleTree::first_contract),
+        }
+    }
+
+    fn render(&self) -> String {
+        self.children
+            .iter()
+            .map(|(name, child)| match &child.contract {
+                Some((_, module)) => module.clone(),
+                None => {
+                    let inner =
+                        child
+                            .render()
+                            .lines()
+                            .map(|line| {
+                                if line.is_empty() {
+                                    String::new()
+                                } else {
+                                    format!("    {}", line)
+                                }
+                            })
+                            .join("\n");
+                    format!("pub mod {} {{\n{}\n}}", name, inner)
+                }
+            })
+            .join("\n\n")
+    }
+}
+
+/// FNV-1a hash of everything the generated bindings depend on: the artifact
+/// and source paths, the ABI and the storage layout.
+fn abi_hash(artifact: &Artifact) -> u64 {
+    let abi = artifact
+        .json
+        .get("abi")
+        .map(Value::to_string)
+        .unwrap_or_default();
+    let layout = artifact
+        .json
+        .get("storageLayout")
+        .map(Value::to_string)
+        .unwrap_or_default();
+
+    fnv1a(
+        [artifact.artifact_path.as_str(), &artifact.source_path, &abi, &layout]
+            .iter()
+            .flat_map(|part| part.bytes().chain([0])),
+    )
+}
+
+/// 64-bit FNV-1a, stable across toolchains unlike `DefaultHasher`.
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes
+        .into_iter()
+        .fold(0xcbf29ce484222325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3))
+}
+
+fn contract_module(artifact: &Artifact) -> String {
+    let storage_module =
+        storage_module(&artifact.contract_name, &artifact.json).unwrap_or_default();
+
+    println!("cargo:warning=Generated bindings for contract: {}", artifact.contract_name);
+
+    format!(
+        r#"#[rustfmt::skip]
 pub mod {} {{
     alloy::sol!(
         #[allow(missing_docs)]
@@ -124,20 +718,216 @@ pub mod {} {{
         #[derive(Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
         {},
         "{}"
-    );
+    );{}
 }}"#,
-                            module_name, contract_name, relative_path
-                        );
+        artifact.module_path.last().unwrap(),
+        artifact.contract_name,
+        artifact.artifact_path,
+        storage_module
+    )
+}
 
-                        contract_modules.push((module_name.clone(), module_content));
+/// Generates the `storage` submodule describing where each state variable of
+/// the contract lives, from the artifact's `storageLayout` output.
+fn storage_module(contract_name: &str, artifact: &Value) -> Option<String> {
+    let layout = artifact.get("storageLayout")?;
+    let storage = layout.get("storage")?.as_array()?;
+    if storage.is_empty() {
+        return None;
+    }
 
-                        println!(
-                            "cargo:warning=Generated bindings for contract: {}",
-                            contract_name
-                        );
-                    }
-                }
+    let types = layout.get("types");
+    let mut constants = Vec::new();
+    let mut names = Vec::new();
+    let mut accessors = Vec::new();
+
+    for variable in storage {
+        let label = variable.get("label")?.as_str()?;
+        let slot = variable.get("slot")?.as_str()?;
+        let offset = variable.get("offset")?.as_u64()?;
+        let type_id = variable.get("type")?.as_str()?;
+        let type_info = types.and_then(|types| types.get(type_id));
+        let type_label = type_info
+            .and_then(|ty| ty.get("label"))
+            .and_then(Value::as_str)
+            .unwrap_or(type_id);
+
+        let name = constant_name(label);
+        constants.push(format!(
+            "        pub const {}: StorageVariable = StorageVariable {{ label: \"{}\", slot: {}, offset: {}, type_label: \"{}\" }};",
+            name,
+            label,
+            u256_literal(slot),
+            offset,
+            type_label
+        ));
+
+        // Accessors are only generated for mappings keyed by a value type,
+        // whose slot is keccak256(pad32(key) ++ slot).
+        if let Some(key_type) = type_info
+            .filter(|ty| ty.get("encoding").and_then(Value::as_str) == Some("mapping"))
+            .and_then(|ty| ty.get("key"))
+            .and_then(Value::as_str)
+            .and_then(mapping_key_type)
+        {
+            accessors.push(format!(
+                r#"        /// Slot of `{label}[key]`.
+        pub fn {label}(key: {key_type}) -> crate::storage_layout::Slot {{
+            crate::storage_layout::mapping_slot(key, {name}.slot)
+        }}"#
+            ));
+        }
+
+        names.push(name);
+    }
+
+    let structs = types
+        .and_then(Value::as_object)
+        .map(|types| types.values().filter_map(packed_struct).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut sections = vec![
+        constants.join("\n"),
+        format!("        pub const LAYOUT: &[StorageVariable] = &[{}];", names.join(", ")),
+    ];
+    sections.extend(accessors);
+    sections.extend(structs);
+
+    Some(format!(
+        r#"
+
+    /// Storage layout of `{}` as reported by solc.
+    pub mod storage {{
+        #![allow(non_snake_case)]
+
+        use crate::storage_layout::StorageVariable;
+
+{}
+    }}"#,
+        contract_name,
+        sections.join("\n\n")
+    ))
+}
+
+/// Rust type used for a mapping key, if it is a value type.
+fn mapping_key_type(type_id: &str) -> Option<&'static str> {
+    match type_id {
+        "t_address" => Some("alloy::primitives::Address"),
+        "t_bytes32" => Some("alloy::primitives::B256"),
+        id if id.starts_with("t_uint") => Some("alloy::primitives::U256"),
+        _ => None,
+    }
+}
+
+/// Rust type used for a packed struct member, if it is a value type.
+fn packed_field_type(type_id: &str) -> Option<String> {
+    match type_id {
+        "t_bool" => Some("bool".to_string()),
+        "t_address" => Some("alloy::primitives::Address".to_string()),
+        id => {
+            if let Some(bits) = id.strip_prefix("t_uint") {
+                let bits: usize = bits.parse().ok()?;
+                return Some(match bits {
+                    8 | 16 | 32 | 64 | 128 => format!("u{}", bits),
+                    _ => format!("alloy::primitives::aliases::U{}", bits),
+                });
             }
+
+            let bytes: usize = id.strip_prefix("t_bytes")?.parse().ok()?;
+            Some(format!("alloy::primitives::FixedBytes<{}>", bytes))
+        }
+    }
+}
+
+/// Generates a struct with `decode_packed`/`encode_packed` for a Solidity
+/// struct that fits in one slot and only holds value types.
+fn packed_struct(type_info: &Value) -> Option<String> {
+    let label = type_info.get("label")?.as_str()?;
+    let name = label.strip_prefix("struct ")?.rsplit('.').next()?;
+    if type_info.get("numberOfBytes")?.as_str()? != "32" {
+        return None;
+    }
+
+    let mut fields = Vec::new();
+    let mut decode = Vec::new();
+    let mut encode = Vec::new();
+
+    for member in type_info.get("members")?.as_array()? {
+        let member_label = member.get("label")?.as_str()?;
+        let offset = member.get("offset")?.as_u64()?;
+        let type_id = member.get("type")?.as_str()?;
+        let rust_type = packed_field_type(type_id)?;
+        let bytes = match type_id {
+            "t_bool" => 1,
+            "t_address" => 20,
+            id if id.starts_with("t_uint") => id["t_uint".len()..].parse::<u64>().ok()? / 8,
+            id => id["t_bytes".len()..].parse().ok()?,
+        };
+
+        fields.push(format!("            pub {}: {},", member_label, rust_type));
+        decode.push(format!(
+            "                    {}: PackedField::from_word(read_packed(word, {}, {})),",
+            member_label, offset, bytes
+        ));
+        encode.push(format!(
+            "                word = write_packed(word, {}, {}, self.{}.to_word());",
+            offset, bytes, member_label
+        ));
+    }
+
+    if fields.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        r#"        /// `{label}`, tightly packed into a single slot.
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+        pub struct {name} {{
+{fields}
+        }}
+
+        impl {name} {{
+            pub fn decode_packed(word: alloy::primitives::U256) -> Self {{
+                use crate::storage_layout::{{PackedField, read_packed}};
+
+                Self {{
+{decode}
+                }}
+            }}
+
+            pub fn encode_packed(&self) -> alloy::primitives::U256 {{
+                use crate::storage_layout::{{PackedField, write_packed}};
+
+                let mut word = alloy::primitives::U256::ZERO;
+{encode}
+                word
+            }}
+        }}"#,
+        fields = fields.join("\n"),
+        decode = decode.join("\n"),
+        encode = encode.join("\n"),
+    ))
+}
+
+/// Upper snake case name for a state variable, keeping leading underscores so
+/// `_a` and `a` stay distinct.
+fn constant_name(label: &str) -> String {
+    let trimmed = label.trim_start_matches('_');
+    let prefix = &label[..label.len() - trimmed.len()];
+    format!("{}{}", prefix, trimmed.to_case(Case::UpperSnake))
+}
+
+/// Renders a decimal slot number as a const `U256` expression.
+fn u256_literal(decimal: &str) -> String {
+    let mut limbs = [0u64; 4];
+    for digit in decimal.bytes() {
+        let mut carry = u128::from(digit - b'0');
+        for limb in limbs.iter_mut() {
+            let value = u128::from(*limb) * 10 + carry;
+            *limb = value as u64;
+            carry = value >> 64;
         }
     }
+
+    format!("alloy::primitives::U256::from_limbs({:?})", limbs)
 }
@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use alloy::{
+    eips::BlockId,
+    primitives::{Address, B256, U256},
+    providers::Provider,
+};
+use revm::{
+    DatabaseRef,
+    primitives::{AccountInfo, Bytecode},
+};
+
+use crate::{environment_deployment::AnvilControls, error::AnvilDbError};
+
+/// Read-through cache over [`AnvilControls`].
+///
+/// Every query is pinned to the block the cache was created at, so the
+/// cached values never go stale while the node keeps mining.
+pub struct AnvilCacheDb {
+    controls: AnvilControls,
+    block_number: u64,
+    /// `None` marks an account known not to exist at the pinned block.
+    accounts: RwLock<HashMap<Address, Option<AccountInfo>>>,
+    code: RwLock<HashMap<B256, Bytecode>>,
+    storage: RwLock<HashMap<(Address, U256), U256>>,
+    block_hashes: RwLock<HashMap<u64, B256>>,
+}
+
+impl AnvilCacheDb {
+    /// Creates a cache pinned to the node's current block.
+    pub async fn new(controls: AnvilControls) -> eyre::Result<Self> {
+        let block_number = controls.provider.get_block_number().await?;
+        Ok(Self::at_block(controls, block_number))
+    }
+
+    /// Creates a cache pinned to `block_number`.
+    pub fn at_block(controls: AnvilControls, block_number: u64) -> Self {
+        Self {
+            controls,
+            block_number,
+            accounts: RwLock::default(),
+            code: RwLock::default(),
+            storage: RwLock::default(),
+            block_hashes: RwLock::default(),
+        }
+    }
+
+    pub fn block_number(&self) -> u64 {
+        self.block_number
+    }
+
+    pub fn controls(&self) -> &AnvilControls {
+        &self.controls
+    }
+
+    pub fn into_inner(self) -> AnvilControls {
+        self.controls
+    }
+
+    /// Loads `slots` of `address` in a single JSON-RPC batch. Slots that are
+    /// already cached are skipped.
+    pub async fn prefetch_storage(
+        &self,
+        address: Address,
+        slots: impl IntoIterator<Item = U256>,
+    ) -> Result<(), AnvilDbError> {
+        let slots = {
+            let storage = read(&self.storage);
+            slots
+                .into_iter()
+                .filter(|slot| !storage.contains_key(&(address, *slot)))
+                .collect::<Vec<_>>()
+        };
+
+        if slots.is_empty() {
+            return Ok(());
+        }
+
+        let values = self
+            .controls
+            .storage_batch(address, &slots, BlockId::number(self.block_number))
+            .await?;
+
+        write(&self.storage).extend(
+            slots
+                .into_iter()
+                .zip(values)
+                .map(|(slot, value)| ((address, slot), value)),
+        );
+
+        Ok(())
+    }
+}
+
+impl DatabaseRef for AnvilCacheDb {
+    type Error = AnvilDbError;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        if let Some(info) = read(&self.accounts).get(&address) {
+            return Ok(info.clone());
+        }
+
+        let info = self
+            .controls
+            .at_block(self.block_number)
+            .basic_ref(address)?;
+
+        if let Some(AccountInfo { code: Some(code), code_hash, .. }) = &info {
+            write(&self.code).insert(*code_hash, code.clone());
+        }
+        write(&self.accounts).insert(address, info.clone());
+
+        Ok(info)
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        if let Some(value) = read(&self.storage).get(&(address, index)) {
+            return Ok(*value);
+        }
+
+        let value = self
+            .controls
+            .at_block(self.block_number)
+            .storage_ref(address, index)?;
+        write(&self.storage).insert((address, index), value);
+
+        Ok(value)
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        if let Some(hash) = read(&self.block_hashes).get(&number) {
+            return Ok(*hash);
+        }
+
+        let hash = self
+            .controls
+            .at_block(self.block_number)
+            .block_hash_ref(number)?;
+        write(&self.block_hashes).insert(number, hash);
+
+        Ok(hash)
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if let Some(code) = read(&self.code).get(&code_hash) {
+            return Ok(code.clone());
+        }
+
+        let code = self
+            .controls
+            .at_block(self.block_number)
+            .code_by_hash_ref(code_hash)?;
+        write(&self.code).insert(code_hash, code.clone());
+
+        Ok(code)
+    }
+}
+
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().expect("anvil cache lock poisoned")
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().expect("anvil cache lock poisoned")
+}
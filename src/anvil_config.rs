@@ -0,0 +1,182 @@
+use std::{
+    env, process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use alloy::node_bindings::Anvil;
+use eyre::bail;
+
+use crate::environment_deployment::{AnvilControls, connect_anvil};
+
+/// How [`AnvilControls`] talks to the spawned node.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AnvilTransport {
+    Http,
+    #[default]
+    Ws,
+    /// A socket at a fresh path in the temp directory, so several instances
+    /// can run side by side.
+    Ipc,
+}
+
+/// Launch options for an anvil node. The defaults are chain id 1, account 7
+/// as signer and auto-mining, over WebSocket, as
+/// [`spin_up_anvil_instance`](crate::environment_deployment::spin_up_anvil_instance)
+/// always used. Unlike it, no IPC socket is opened unless the transport is
+/// [`AnvilTransport::Ipc`], since instances sharing anvil's default socket
+/// path clash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnvilConfig {
+    pub chain_id: u64,
+    /// Hardfork name as understood by `anvil --hardfork`, e.g. `"cancun"`.
+    pub hardfork: Option<String>,
+    /// Seconds between blocks; `None` mines on demand only.
+    pub block_time: Option<u64>,
+    /// Mines a block for every transaction. Turning this off leaves
+    /// transactions pending until mined through the API; anvil does not
+    /// accept it together with a `block_time`, which already replaces
+    /// auto-mining with interval mining.
+    pub auto_mine: bool,
+    pub gas_limit: Option<u64>,
+    pub base_fee: Option<u64>,
+    pub accounts: Option<u32>,
+    pub mnemonic: Option<String>,
+    /// Dev account deployments are sent from.
+    pub signer_index: usize,
+    pub transport: AnvilTransport,
+    pub fork_url: Option<String>,
+}
+
+impl Default for AnvilConfig {
+    fn default() -> Self {
+        Self {
+            chain_id: 1,
+            hardfork: None,
+            block_time: None,
+            auto_mine: true,
+            gas_limit: None,
+            base_fee: None,
+            accounts: None,
+            mnemonic: None,
+            signer_index: 7,
+            transport: AnvilTransport::default(),
+            fork_url: None,
+        }
+    }
+}
+
+impl AnvilConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    pub fn hardfork(mut self, hardfork: impl Into<String>) -> Self {
+        self.hardfork = Some(hardfork.into());
+        self
+    }
+
+    pub fn block_time(mut self, seconds: u64) -> Self {
+        self.block_time = Some(seconds);
+        self
+    }
+
+    pub fn auto_mine(mut self, auto_mine: bool) -> Self {
+        self.auto_mine = auto_mine;
+        self
+    }
+
+    pub fn gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = Some(gas_limit);
+        self
+    }
+
+    pub fn base_fee(mut self, base_fee: u64) -> Self {
+        self.base_fee = Some(base_fee);
+        self
+    }
+
+    pub fn accounts(mut self, accounts: u32) -> Self {
+        self.accounts = Some(accounts);
+        self
+    }
+
+    pub fn mnemonic(mut self, mnemonic: impl Into<String>) -> Self {
+        self.mnemonic = Some(mnemonic.into());
+        self
+    }
+
+    pub fn signer_index(mut self, signer_index: usize) -> Self {
+        self.signer_index = signer_index;
+        self
+    }
+
+    pub fn transport(mut self, transport: AnvilTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    pub fn fork(mut self, fork_url: impl Into<String>) -> Self {
+        self.fork_url = Some(fork_url.into());
+        self
+    }
+
+    /// Spawns anvil with these options and connects to it.
+    pub async fn spawn(&self) -> eyre::Result<AnvilControls> {
+        if !self.auto_mine && self.block_time.is_some() {
+            bail!("anvil cannot disable mining while a block time is set");
+        }
+
+        let mut anvil = Anvil::new().chain_id(self.chain_id);
+
+        if let Some(hardfork) = &self.hardfork {
+            anvil = anvil.args(["--hardfork", hardfork.as_str()]);
+        }
+        if let Some(block_time) = self.block_time {
+            anvil = anvil.block_time(block_time);
+        }
+        if !self.auto_mine {
+            anvil = anvil.arg("--no-mining");
+        }
+        if let Some(gas_limit) = self.gas_limit {
+            anvil = anvil.args(["--gas-limit".to_string(), gas_limit.to_string()]);
+        }
+        if let Some(base_fee) = self.base_fee {
+            anvil = anvil.args(["--base-fee".to_string(), base_fee.to_string()]);
+        }
+        if let Some(accounts) = self.accounts {
+            anvil = anvil.args(["--accounts".to_string(), accounts.to_string()]);
+        }
+        if let Some(mnemonic) = &self.mnemonic {
+            anvil = anvil.mnemonic(mnemonic);
+        }
+        if let Some(fork_url) = &self.fork_url {
+            anvil = anvil.fork(fork_url);
+        }
+
+        let ipc_path = (self.transport == AnvilTransport::Ipc).then(fresh_ipc_path);
+        if let Some(ipc_path) = &ipc_path {
+            anvil = anvil.args(["--ipc", ipc_path.as_str()]);
+        }
+
+        let anvil = anvil.try_spawn()?;
+        let endpoint = match ipc_path {
+            Some(ipc_path) => ipc_path,
+            None if self.transport == AnvilTransport::Http => anvil.endpoint(),
+            None => anvil.ws_endpoint(),
+        };
+
+        connect_anvil(anvil, self.signer_index, &endpoint).await
+    }
+}
+
+fn fresh_ipc_path() -> String {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let name = format!("anvil-{}-{}.ipc", process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+    env::temp_dir().join(name).to_string_lossy().into_owned()
+}
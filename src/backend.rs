@@ -0,0 +1,198 @@
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use alloy::primitives::{Address, B256, U256};
+use revm::{
+    DatabaseRef,
+    primitives::{AccountInfo, Bytecode},
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    Payload,
+    environment_deployment::{
+        AnvilControls, deploy_lock_contract, spin_up_anvil_instance, spin_up_fork_instance,
+    },
+    error::AnvilDbError,
+    in_memory::{InMemoryDb, deploy_lock_contract_in_memory},
+};
+
+/// Somewhere `GateLock` can be deployed and then read back by a solver.
+pub trait SolverBackend {
+    type Db: DatabaseRef;
+
+    fn name(&self) -> &'static str;
+
+    /// Deploys `GateLock` with `payload`, returning its address and a database
+    /// view of the resulting state.
+    fn deploy(
+        &self,
+        payload: Vec<Payload>,
+    ) -> impl Future<Output = eyre::Result<(Address, Self::Db)>>;
+}
+
+/// Spawns a fresh anvil process per deployment.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnvilBackend;
+
+impl SolverBackend for AnvilBackend {
+    type Db = AnvilControls;
+
+    fn name(&self) -> &'static str {
+        "anvil"
+    }
+
+    async fn deploy(&self, payload: Vec<Payload>) -> eyre::Result<(Address, AnvilControls)> {
+        let controls = spin_up_anvil_instance().await?;
+        let address = deploy_lock_contract(&controls, payload).await?;
+        Ok((address, controls))
+    }
+}
+
+/// Deploys every payload on one long-lived anvil process, so many trials do
+/// not each pay for spawning a node. The chain is reverted to a snapshot of
+/// its state at creation before each deployment, so every trial starts clean.
+///
+/// A deployment invalidates the state handed to the previous solver, so the
+/// solver must be done with it before deploying again.
+#[derive(Clone)]
+pub struct SharedAnvilBackend {
+    controls: Arc<AnvilControls>,
+    clean: Arc<Mutex<U256>>,
+}
+
+impl SharedAnvilBackend {
+    pub async fn new(controls: AnvilControls) -> eyre::Result<Self> {
+        let clean = controls.snapshot().await?;
+        Ok(Self { controls: Arc::new(controls), clean: Arc::new(Mutex::new(clean)) })
+    }
+
+    pub async fn spawn() -> eyre::Result<Self> {
+        Self::new(spin_up_anvil_instance().await?).await
+    }
+
+    /// Rolls the chain back to its state when the backend was created.
+    pub async fn reset(&self) -> eyre::Result<()> {
+        self.revert_to(&mut *self.clean.lock().await).await
+    }
+
+    /// Reverts to the `clean` snapshot and takes a new one, since anvil drops
+    /// a snapshot once it is reverted to. The caller holds the lock on `clean`.
+    async fn revert_to(&self, clean: &mut U256) -> eyre::Result<()> {
+        self.controls.revert(*clean).await?;
+        *clean = self.controls.snapshot().await?;
+        Ok(())
+    }
+
+    pub fn controls(&self) -> &Arc<AnvilControls> {
+        &self.controls
+    }
+}
+
+impl SolverBackend for SharedAnvilBackend {
+    type Db = Arc<AnvilControls>;
+
+    fn name(&self) -> &'static str {
+        "shared-anvil"
+    }
+
+    async fn deploy(&self, payload: Vec<Payload>) -> eyre::Result<(Address, Arc<AnvilControls>)> {
+        // Clones share the lock, so it is held until the contract is deployed
+        // to keep another deployment from reverting this one half way.
+        let mut clean = self.clean.lock().await;
+        self.revert_to(&mut clean).await?;
+        let address = deploy_lock_contract(&self.controls, payload).await?;
+        Ok((address, self.controls.clone()))
+    }
+}
+
+/// Deploys into an in-process REVM database.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InMemoryBackend;
+
+impl SolverBackend for InMemoryBackend {
+    type Db = InMemoryDb;
+
+    fn name(&self) -> &'static str {
+        "in-memory"
+    }
+
+    async fn deploy(&self, payload: Vec<Payload>) -> eyre::Result<(Address, InMemoryDb)> {
+        deploy_lock_contract_in_memory(payload)
+    }
+}
+
+/// Deploys on one anvil node and hands the solver a second node forking it,
+/// so every read goes through the lazy remote-state path of a fork.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ForkedAnvilBackend;
+
+/// The fork node read by the solver, along with the upstream it forks.
+pub struct ForkedAnvil {
+    pub upstream: AnvilControls,
+    pub fork: AnvilControls,
+}
+
+impl SolverBackend for ForkedAnvilBackend {
+    type Db = ForkedAnvil;
+
+    fn name(&self) -> &'static str {
+        "forked-anvil"
+    }
+
+    async fn deploy(&self, payload: Vec<Payload>) -> eyre::Result<(Address, ForkedAnvil)> {
+        let upstream = spin_up_anvil_instance().await?;
+        let address = deploy_lock_contract(&upstream, payload).await?;
+        let fork = spin_up_fork_instance(upstream.instance.endpoint()).await?;
+
+        Ok((address, ForkedAnvil { upstream, fork }))
+    }
+}
+
+impl DatabaseRef for ForkedAnvil {
+    type Error = AnvilDbError;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.fork.basic_ref(address)
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.fork.code_by_hash_ref(code_hash)
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.fork.storage_ref(address, index)
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        self.fork.block_hash_ref(number)
+    }
+}
+
+/// Result of running a solver once against a backend.
+#[derive(Debug)]
+pub struct BackendRun {
+    pub backend: &'static str,
+    pub solved: eyre::Result<bool>,
+    pub elapsed: Duration,
+}
+
+/// Deploys `payload` on `backend` and runs the solver against it. Running the
+/// same payload on several backends lets their results be compared.
+pub async fn run_on_backend<B, F, O>(backend: &B, payload: Vec<Payload>, f: F) -> BackendRun
+where
+    B: SolverBackend,
+    F: FnOnce(Address, B::Db) -> O,
+    O: Future<Output = eyre::Result<bool>>,
+{
+    let start = Instant::now();
+    let solved = match backend.deploy(payload).await {
+        Ok((address, db)) => f(address, db).await,
+        Err(e) => Err(e),
+    };
+
+    BackendRun { backend: backend.name(), solved, elapsed: start.elapsed() }
+}
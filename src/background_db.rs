@@ -0,0 +1,270 @@
+use std::{
+    sync::{
+        Arc,
+        mpsc::{self as std_mpsc, RecvTimeoutError},
+    },
+    thread,
+    time::Duration,
+};
+
+use alloy::{
+    eips::BlockId,
+    network::Ethereum,
+    primitives::{Address, B256, Bytes, U256},
+    providers::{Provider, RootProvider, builder},
+    rpc::types::{BlockNumberOrTag, BlockTransactionsKind},
+    transports::BoxTransport,
+};
+use eyre::eyre;
+use revm::{
+    DatabaseRef,
+    primitives::{AccountInfo, Bytecode, KECCAK_EMPTY},
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    environment_deployment::{AnvilControls, CodeIndex},
+    error::{AnvilDbError, Query},
+};
+
+type Reply<T> = std_mpsc::SyncSender<Result<T, AnvilDbError>>;
+
+/// How long a lookup waits for the background thread before giving up.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Queries the background thread knows how to answer.
+enum Request {
+    Basic { address: Address, block: BlockId, reply: Reply<AccountInfo> },
+    Storage { address: Address, index: U256, block: BlockId, reply: Reply<U256> },
+    BlockHash { number: u64, reply: Reply<B256> },
+    Code { address: Address, block: BlockId, reply: Reply<Bytes> },
+}
+
+/// Database backend that drives its own provider on a dedicated thread.
+///
+/// Unlike the [`DatabaseRef`] impl on [`AnvilControls`], lookups do not need
+/// the caller to be on a multi-threaded tokio runtime: requests are queued to
+/// a background current-thread runtime and the caller blocks on the reply,
+/// so this works from any runtime flavour as well as from plain sync code.
+#[derive(Clone)]
+pub struct AnvilBackgroundDb {
+    requests: mpsc::UnboundedSender<Request>,
+    block: BlockId,
+    timeout: Duration,
+    code_index: Arc<CodeIndex>,
+}
+
+impl AnvilBackgroundDb {
+    /// Spawns the background thread and connects it to `endpoint`.
+    pub fn connect(endpoint: impl Into<String>) -> eyre::Result<Self> {
+        let endpoint = endpoint.into();
+        let (requests, receiver) = mpsc::unbounded_channel();
+        let (ready_tx, ready_rx) = std_mpsc::sync_channel::<eyre::Result<()>>(1);
+
+        thread::Builder::new()
+            .name("anvil-background-db".into())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e.into()));
+                        return;
+                    }
+                };
+
+                runtime.block_on(async move {
+                    match builder::<Ethereum>().on_builtin(&endpoint).await {
+                        Ok(provider) => {
+                            let _ = ready_tx.send(Ok(()));
+                            serve(provider, receiver).await;
+                        }
+                        Err(e) => {
+                            let _ = ready_tx.send(Err(e.into()));
+                        }
+                    }
+                });
+            })?;
+
+        ready_rx
+            .recv()
+            .map_err(|_| eyre!("anvil background thread exited during startup"))??;
+
+        Ok(Self {
+            requests,
+            block: BlockId::latest(),
+            timeout: DEFAULT_TIMEOUT,
+            code_index: Arc::default(),
+        })
+    }
+
+    /// Returns a handle sharing the same background thread whose queries all
+    /// target block `number`.
+    pub fn at_block(&self, number: u64) -> Self {
+        Self { block: BlockId::number(number), ..self.clone() }
+    }
+
+    /// Sets how long each lookup waits for a reply.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    fn request<T>(
+        &self,
+        query: Query,
+        request: impl FnOnce(Reply<T>) -> Request,
+    ) -> Result<T, AnvilDbError> {
+        let (reply, response) = std_mpsc::sync_channel(1);
+        self.requests
+            .send(request(reply))
+            .map_err(|_| AnvilDbError::Disconnected)?;
+
+        match response.recv_timeout(self.timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => {
+                Err(AnvilDbError::Timeout { query, after: self.timeout })
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(AnvilDbError::Disconnected),
+        }
+    }
+}
+
+impl AnvilControls {
+    /// Connects an [`AnvilBackgroundDb`] to this node over HTTP.
+    pub fn background_db(&self) -> eyre::Result<AnvilBackgroundDb> {
+        AnvilBackgroundDb::connect(self.instance.endpoint())
+    }
+}
+
+impl DatabaseRef for AnvilBackgroundDb {
+    type Error = AnvilDbError;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let block = self.block;
+        let mut info = self.request(Query::Account { address, block }, |reply| Request::Basic {
+            address,
+            block,
+            reply,
+        })?;
+
+        if info.is_empty() {
+            return Ok(None);
+        }
+
+        // The code is only fetched once REVM asks for it by hash, from the
+        // owner recorded here, unless it is already known.
+        self.code_index.record_owner(address, info.code_hash);
+        info.code = self.code_index.code(info.code_hash);
+
+        Ok(Some(info))
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        let block = self.block;
+        self.request(Query::Storage { address, index, block }, |reply| Request::Storage {
+            address,
+            index,
+            block,
+            reply,
+        })
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        self.request(Query::BlockHash { number }, |reply| Request::BlockHash { number, reply })
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        if code_hash == KECCAK_EMPTY {
+            return Ok(Bytecode::default());
+        }
+
+        if let Some(code) = self.code_index.code(code_hash) {
+            return Ok(code);
+        }
+
+        let Some(address) = self.code_index.owner(code_hash) else {
+            return Err(AnvilDbError::UnknownCodeHash { code_hash });
+        };
+        let block = self.block;
+        let code = self.request(Query::Code { address, block }, |reply| Request::Code {
+            address,
+            block,
+            reply,
+        })?;
+        if code.is_empty() {
+            return Err(AnvilDbError::MissingAccount { address, block });
+        }
+        let code = Bytecode::new_raw(code);
+        if code.hash_slow() != code_hash {
+            return Err(AnvilDbError::CodeHashMismatch { address, code_hash });
+        }
+
+        self.code_index.insert(address, code_hash, code.clone());
+        Ok(code)
+    }
+}
+
+async fn serve(
+    provider: RootProvider<BoxTransport>,
+    mut requests: mpsc::UnboundedReceiver<Request>,
+) {
+    while let Some(request) = requests.recv().await {
+        let provider = provider.clone();
+        tokio::spawn(async move { request.handle(&provider).await });
+    }
+}
+
+impl Request {
+    async fn handle(self, provider: &RootProvider<BoxTransport>) {
+        match self {
+            Request::Basic { address, block, reply } => {
+                let _ = reply.send(fetch_account(provider, address, block).await);
+            }
+            Request::Storage { address, index, block, reply } => {
+                let value = provider
+                    .get_storage_at(address, index)
+                    .block_id(block)
+                    .await;
+                let query = Query::Storage { address, index, block };
+                let _ = reply.send(value.map_err(|e| AnvilDbError::transport(query, e)));
+            }
+            Request::BlockHash { number, reply } => {
+                let _ = reply.send(fetch_block_hash(provider, number).await);
+            }
+            Request::Code { address, block, reply } => {
+                let code = provider.get_code_at(address).block_id(block).await;
+                let query = Query::Code { address, block };
+                let _ = reply.send(code.map_err(|e| AnvilDbError::transport(query, e)));
+            }
+        }
+    }
+}
+
+async fn fetch_account(
+    provider: &RootProvider<BoxTransport>,
+    address: Address,
+    block: BlockId,
+) -> Result<AccountInfo, AnvilDbError> {
+    let acc = provider
+        .get_account(address)
+        .block_id(block)
+        .await
+        .map_err(|e| AnvilDbError::transport(Query::Account { address, block }, e))?;
+
+    Ok(AccountInfo { code_hash: acc.code_hash, balance: acc.balance, nonce: acc.nonce, code: None })
+}
+
+async fn fetch_block_hash(
+    provider: &RootProvider<BoxTransport>,
+    number: u64,
+) -> Result<B256, AnvilDbError> {
+    let block = provider
+        .get_block_by_number(BlockNumberOrTag::Number(number), BlockTransactionsKind::Hashes)
+        .await
+        .map_err(|e| AnvilDbError::transport(Query::BlockHash { number }, e))?;
+
+    let Some(block) = block else { return Err(AnvilDbError::MissingBlock { number }) };
+    Ok(block.header.hash)
+}
@@ -0,0 +1,77 @@
+pub mod gate_lock_sol {
+    #[rustfmt::skip]
+    pub mod gate_lock {
+        alloy::sol!(
+            #[allow(missing_docs)]
+            #[sol(rpc, abi)]
+            #[derive(Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+            GateLock,
+            "$OUT_DIR/artifacts/GateLock.sol/GateLock.json"
+        );
+
+        /// Storage layout of `GateLock` as reported by solc.
+        pub mod storage {
+            #![allow(non_snake_case)]
+
+            use crate::storage_layout::StorageVariable;
+
+            pub const _A: StorageVariable = StorageVariable { label: "_a", slot: alloy::primitives::U256::from_limbs([0, 0, 0, 0]), offset: 0, type_label: "mapping(uint256 => uint64)" };
+            pub const _B: StorageVariable = StorageVariable { label: "_b", slot: alloy::primitives::U256::from_limbs([1, 0, 0, 0]), offset: 0, type_label: "mapping(address => uint56)" };
+            pub const VALUE_MAP: StorageVariable = StorageVariable { label: "valueMap", slot: alloy::primitives::U256::from_limbs([2, 0, 0, 0]), offset: 0, type_label: "mapping(uint256 => struct GateLock.Values)" };
+            pub const _C: StorageVariable = StorageVariable { label: "_c", slot: alloy::primitives::U256::from_limbs([3, 0, 0, 0]), offset: 0, type_label: "mapping(bytes32 => uint128)" };
+            pub const TOTAL_LENGTH: StorageVariable = StorageVariable { label: "totalLength", slot: alloy::primitives::U256::from_limbs([4, 0, 0, 0]), offset: 0, type_label: "uint256" };
+
+            pub const LAYOUT: &[StorageVariable] = &[_A, _B, VALUE_MAP, _C, TOTAL_LENGTH];
+
+            /// Slot of `_a[key]`.
+            pub fn _a(key: alloy::primitives::U256) -> crate::storage_layout::Slot {
+                crate::storage_layout::mapping_slot(key, _A.slot)
+            }
+
+            /// Slot of `_b[key]`.
+            pub fn _b(key: alloy::primitives::Address) -> crate::storage_layout::Slot {
+                crate::storage_layout::mapping_slot(key, _B.slot)
+            }
+
+            /// Slot of `valueMap[key]`.
+            pub fn valueMap(key: alloy::primitives::U256) -> crate::storage_layout::Slot {
+                crate::storage_layout::mapping_slot(key, VALUE_MAP.slot)
+            }
+
+            /// Slot of `_c[key]`.
+            pub fn _c(key: alloy::primitives::B256) -> crate::storage_layout::Slot {
+                crate::storage_layout::mapping_slot(key, _C.slot)
+            }
+
+            /// `struct GateLock.Values`, tightly packed into a single slot.
+            #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+            pub struct Values {
+                pub firstValue: u64,
+                pub secondValue: alloy::primitives::aliases::U160,
+                pub is_unlocked: bool,
+            }
+
+            impl Values {
+                pub fn decode_packed(word: alloy::primitives::U256) -> Self {
+                    use crate::storage_layout::{PackedField, read_packed};
+
+                    Self {
+                        firstValue: PackedField::from_word(read_packed(word, 0, 8)),
+                        secondValue: PackedField::from_word(read_packed(word, 8, 20)),
+                        is_unlocked: PackedField::from_word(read_packed(word, 28, 1)),
+                    }
+                }
+
+                pub fn encode_packed(&self) -> alloy::primitives::U256 {
+                    use crate::storage_layout::{PackedField, write_packed};
+
+                    let mut word = alloy::primitives::U256::ZERO;
+                    word = write_packed(word, 0, 8, self.firstValue.to_word());
+                    word = write_packed(word, 8, 20, self.secondValue.to_word());
+                    word = write_packed(word, 28, 1, self.is_unlocked.to_word());
+                    word
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,7 @@
-#[rustfmt::skip]
-pub mod gate_lock {
-    alloy::sol!(
-        #[allow(missing_docs)]
-        #[sol(rpc, abi)]
-        #[derive(Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
-        GateLock,
-        "contracts/out/GateLock.sol/GateLock.json"
-    );
-}
+//! Contract bindings generated by `build.rs` from the forge artifacts.
+//! `generated.rs` next to this file is a checked-in copy for reading and
+//! review; it is refreshed with `CONTRACT_BINDINGS=write cargo build` and
+//! verified with `CONTRACT_BINDINGS=check cargo build`. Its artifact paths
+//! are shown relative to `$OUT_DIR`, where the contracts are compiled.
+
+include!(concat!(env!("OUT_DIR"), "/contract_bindings.rs"));
@@ -0,0 +1,280 @@
+use std::fmt;
+
+use alloy::{
+    eips::BlockId,
+    network::TransactionBuilder,
+    primitives::{Address, Bytes, U256},
+    providers::{Provider, ext::DebugApi},
+    rpc::{
+        json_rpc::RpcError,
+        types::{
+            TransactionRequest,
+            state::StateOverride,
+            trace::geth::{
+                GethDebugTracingCallOptions, GethDebugTracingOptions, GethDefaultTracingOptions,
+                GethTrace,
+            },
+        },
+    },
+    sol_types::decode_revert_reason,
+};
+use eyre::bail;
+use revm::{
+    DatabaseRef, Evm,
+    primitives::{EnvWithHandlerCfg, ExecutionResult, TransactTo},
+};
+
+use crate::{environment_deployment::AnvilControls, overlay_db::OverlayDb};
+
+/// What one side of a differential call produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallOutcome {
+    pub success: bool,
+    /// Return data, or revert data for a failed call.
+    pub output: Bytes,
+    pub gas_used: u64,
+    /// Decoded `Error(string)` or `Panic(uint256)` revert data.
+    pub revert_reason: Option<String>,
+    /// Why execution halted without reverting, e.g. running out of gas. The
+    /// wording differs between REVM and anvil, so only its presence is
+    /// compared.
+    pub halt: Option<String>,
+}
+
+impl CallOutcome {
+    fn reverted(output: Bytes, gas_used: u64) -> Self {
+        let revert_reason = decode_revert_reason(&output);
+        Self { success: false, output, gas_used, revert_reason, halt: None }
+    }
+}
+
+/// A way the REVM and anvil outcomes of the same call disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    Success { revm: bool, anvil: bool },
+    Output { revm: Bytes, anvil: Bytes },
+    GasUsed { revm: u64, anvil: u64 },
+    RevertReason { revm: Option<String>, anvil: Option<String> },
+    Halt { revm: Option<String>, anvil: Option<String> },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Discrepancy::Success { revm, anvil } => {
+                write!(f, "success differs: revm {revm}, anvil {anvil}")
+            }
+            Discrepancy::Output { revm, anvil } => {
+                write!(f, "output differs: revm {revm}, anvil {anvil}")
+            }
+            Discrepancy::GasUsed { revm, anvil } => {
+                write!(f, "gas used differs: revm {revm}, anvil {anvil}")
+            }
+            Discrepancy::RevertReason { revm, anvil } => {
+                write!(f, "revert reason differs: revm {revm:?}, anvil {anvil:?}")
+            }
+            Discrepancy::Halt { revm, anvil } => {
+                write!(f, "halt differs: revm {revm:?}, anvil {anvil:?}")
+            }
+        }
+    }
+}
+
+/// The same call executed in REVM and on anvil.
+#[derive(Debug, Clone)]
+pub struct DifferentialReport {
+    pub revm: CallOutcome,
+    pub anvil: CallOutcome,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl DifferentialReport {
+    pub fn new(revm: CallOutcome, anvil: CallOutcome) -> Self {
+        let mut discrepancies = Vec::new();
+        if revm.success != anvil.success {
+            discrepancies.push(Discrepancy::Success { revm: revm.success, anvil: anvil.success });
+        }
+        if revm.output != anvil.output {
+            discrepancies.push(Discrepancy::Output {
+                revm: revm.output.clone(),
+                anvil: anvil.output.clone(),
+            });
+        }
+        if revm.gas_used != anvil.gas_used {
+            discrepancies.push(Discrepancy::GasUsed { revm: revm.gas_used, anvil: anvil.gas_used });
+        }
+        if revm.revert_reason != anvil.revert_reason {
+            discrepancies.push(Discrepancy::RevertReason {
+                revm: revm.revert_reason.clone(),
+                anvil: anvil.revert_reason.clone(),
+            });
+        }
+        if revm.halt.is_some() != anvil.halt.is_some() {
+            discrepancies
+                .push(Discrepancy::Halt { revm: revm.halt.clone(), anvil: anvil.halt.clone() });
+        }
+
+        Self { revm, anvil, discrepancies }
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
+
+impl fmt::Display for DifferentialReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_consistent() {
+            return write!(f, "revm and anvil agree");
+        }
+
+        write!(f, "revm and anvil disagree:")?;
+        for discrepancy in &self.discrepancies {
+            write!(f, "\n  {discrepancy}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Calls `to` with `data` from the zero address in REVM over `db`. The base
+/// fee check is disabled as `eth_call` does, so the call needs no funds.
+pub fn call_revm<DB>(
+    db: DB,
+    mut env: EnvWithHandlerCfg,
+    to: Address,
+    data: Bytes,
+    gas_limit: u64,
+) -> eyre::Result<CallOutcome>
+where
+    DB: DatabaseRef,
+    DB::Error: fmt::Debug,
+{
+    env.cfg.disable_base_fee = true;
+    env.tx.caller = Address::ZERO;
+    env.tx.transact_to = TransactTo::Call(to);
+    env.tx.data = data;
+    env.tx.gas_limit = gas_limit;
+    env.tx.gas_price = U256::ZERO;
+
+    let result = Evm::builder()
+        .with_ref_db(db)
+        .with_env_with_handler_cfg(env)
+        .build()
+        .transact()
+        .map_err(|e| eyre::eyre!("REVM execution failed: {:?}", e))?
+        .result;
+
+    Ok(match result {
+        ExecutionResult::Success { output, gas_used, .. } => CallOutcome {
+            success: true,
+            output: output.into_data(),
+            gas_used,
+            revert_reason: None,
+            halt: None,
+        },
+        ExecutionResult::Revert { output, gas_used } => CallOutcome::reverted(output, gas_used),
+        ExecutionResult::Halt { reason, gas_used } => CallOutcome {
+            success: false,
+            output: Bytes::new(),
+            gas_used,
+            revert_reason: None,
+            halt: Some(format!("{reason:?}")),
+        },
+    })
+}
+
+/// Calls `to` with `data` from the zero address on top of anvil's `block`
+/// with `overrides` applied. Return and revert data come from `eth_call` and
+/// gas used from `debug_traceCall`, which reports it where `eth_call` does
+/// not. Both run at `block`.
+pub async fn call_anvil(
+    controls: &AnvilControls,
+    block: BlockId,
+    overrides: &StateOverride,
+    to: Address,
+    data: Bytes,
+    gas_limit: u64,
+) -> eyre::Result<CallOutcome> {
+    let tx = TransactionRequest::default()
+        .with_from(Address::ZERO)
+        .with_to(to)
+        .with_input(data)
+        .with_gas_limit(gas_limit);
+
+    let options = GethDebugTracingCallOptions {
+        tracing_options: GethDebugTracingOptions {
+            config: GethDefaultTracingOptions::default()
+                .disable_storage()
+                .disable_stack(),
+            ..Default::default()
+        },
+        state_overrides: Some(overrides.clone()),
+        ..Default::default()
+    };
+    let trace = controls
+        .provider
+        .debug_trace_call(tx.clone(), block, options)
+        .await?;
+    let GethTrace::Default(frame) = trace else {
+        bail!("debug_traceCall did not return a struct log trace");
+    };
+
+    match controls
+        .provider
+        .call(&tx)
+        .block(block)
+        .overrides(overrides)
+        .await
+    {
+        Ok(output) => Ok(CallOutcome {
+            success: true,
+            output,
+            gas_used: frame.gas,
+            revert_reason: None,
+            halt: None,
+        }),
+        Err(RpcError::ErrorResp(payload)) => match payload.as_revert_data() {
+            Some(output) => Ok(CallOutcome::reverted(output, frame.gas)),
+            None => Ok(CallOutcome {
+                success: false,
+                output: Bytes::new(),
+                gas_used: frame.gas,
+                revert_reason: None,
+                halt: Some(payload.message.to_string()),
+            }),
+        },
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Runs the same call in REVM over `overlay` and on anvil with the overlay's
+/// overrides, both on top of block `block_number`, reporting where the two
+/// disagree. `overlay` should wrap
+/// [`controls.at_block(block_number)`](AnvilControls::at_block) (or a cache
+/// over it) so both sides start from the same state.
+pub async fn differential_call<DB>(
+    controls: &AnvilControls,
+    block_number: u64,
+    overlay: &OverlayDb<DB>,
+    to: Address,
+    data: Bytes,
+    gas_limit: u64,
+) -> eyre::Result<DifferentialReport>
+where
+    DB: DatabaseRef,
+    DB::Error: fmt::Debug,
+{
+    let env = controls.revm_env_at(block_number).await?;
+    let revm = call_revm(overlay, env, to, data.clone(), gas_limit)?;
+    let anvil = call_anvil(
+        controls,
+        BlockId::number(block_number),
+        &overlay.state_override(),
+        to,
+        data,
+        gas_limit,
+    )
+    .await?;
+
+    Ok(DifferentialReport::new(revm, anvil))
+}
@@ -1,18 +1,45 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::RwLock,
+};
+
 use alloy::{
-    network::{Ethereum, EthereumWallet},
-    node_bindings::{Anvil, AnvilInstance},
-    primitives::{Address, B256, U256},
-    providers::{Identity, Provider, RootProvider, builder, fillers::*},
+    eips::BlockId,
+    network::{Ethereum, EthereumWallet, TransactionBuilder},
+    node_bindings::AnvilInstance,
+    primitives::{Address, B256, TxHash, U256},
+    providers::{
+        Identity, Provider, RootProvider, builder,
+        ext::{AnvilApi, DebugApi},
+        fillers::*,
+    },
+    rpc::{
+        client::BatchRequest,
+        types::{
+            TransactionRequest,
+            state::{AccountOverride as RpcAccountOverride, StateOverride},
+            trace::geth::{
+                DiffMode, GethDebugBuiltInTracerType, GethDebugTracerType, GethDebugTracingOptions,
+                GethTrace, PreStateConfig, PreStateFrame,
+            },
+        },
+    },
     signers::local::PrivateKeySigner,
+    sol_types::SolCall,
     transports::BoxTransport,
 };
 use eyre::bail;
 use revm::{
     DatabaseRef,
-    primitives::{self, Bytecode},
+    primitives::{self, Bytecode, KECCAK_EMPTY},
 };
 
-use crate::contract_bindings::gate_lock::GateLock;
+use crate::{
+    anvil_config::AnvilConfig,
+    contract_bindings::gate_lock_sol::gate_lock::{GateLock, storage},
+    error::{AnvilDbError, Query},
+    overlay_db::AccountOverride,
+};
 
 pub type AnvilProvider = FillProvider<
     JoinFill<
@@ -28,68 +55,411 @@ pub type AnvilProvider = FillProvider<
 >;
 
 pub async fn spin_up_anvil_instance() -> eyre::Result<AnvilControls> {
-    let anvil = Anvil::new().chain_id(1).arg("--ipc").try_spawn()?;
+    AnvilConfig::default().spawn().await
+}
 
-    let sk: PrivateKeySigner = anvil.keys()[7].clone().into();
+/// Spawns an anvil instance forking the node at `fork_url`.
+pub async fn spin_up_fork_instance(fork_url: impl Into<String>) -> eyre::Result<AnvilControls> {
+    AnvilConfig::default().fork(fork_url).spawn().await
+}
+
+/// Connects to `anvil` at `endpoint`, signing with its dev account
+/// `signer_index`.
+pub(crate) async fn connect_anvil(
+    anvil: AnvilInstance,
+    signer_index: usize,
+    endpoint: &str,
+) -> eyre::Result<AnvilControls> {
+    let Some(key) = anvil.keys().get(signer_index) else {
+        bail!("anvil has {} accounts, no signer at index {signer_index}", anvil.keys().len());
+    };
+    let sk: PrivateKeySigner = key.clone().into();
 
     let wallet = EthereumWallet::new(sk);
 
     let rpc = builder::<Ethereum>()
         .with_recommended_fillers()
         .wallet(wallet.clone())
-        .on_builtin(&anvil.ws_endpoint())
+        .on_builtin(endpoint)
         .await?;
 
-    Ok(AnvilControls { provider: rpc, wallet, instance: anvil })
+    Ok(AnvilControls { provider: rpc, wallet, instance: anvil, code_index: CodeIndex::default() })
 }
 
 pub async fn deploy_lock_contract(
     controls: &AnvilControls,
     payload: Vec<crate::Payload>,
 ) -> eyre::Result<Address> {
-    // Convert our Payload to the contract's Payload format
-    let contract_payloads: Vec<GateLock::Payload> = payload
+    // Deploy the contract with the payload
+    let contract = GateLock::deploy(&controls.provider, contract_payloads(payload)).await?;
+
+    Ok(*contract.address())
+}
+
+/// Converts our Payload to the contract's Payload format.
+pub(crate) fn contract_payloads(payload: Vec<crate::Payload>) -> Vec<GateLock::Payload> {
+    payload
         .into_iter()
         .map(|p| GateLock::Payload { firstValue: p.firstValue, secondValue: p.secondValue })
-        .collect();
+        .collect()
+}
 
-    // Deploy the contract with the payload
-    let contract = GateLock::deploy(&controls.provider, contract_payloads).await?;
+/// A storage slot written by a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotDiff {
+    pub slot: B256,
+    pub old: B256,
+    pub new: B256,
+}
 
-    Ok(*contract.address())
+/// Storage slots written per account, ordered by address and slot.
+pub type StorageDiff = BTreeMap<Address, Vec<SlotDiff>>;
+
+/// A deployment along with every storage slot its constructor wrote.
+#[derive(Debug, Clone)]
+pub struct DeploymentDiff {
+    pub address: Address,
+    pub tx_hash: TxHash,
+    pub storage: StorageDiff,
+}
+
+/// Deploys `GateLock` like [`deploy_lock_contract`], then traces the creation
+/// transaction to report which slots the constructor populated.
+pub async fn deploy_lock_contract_with_diff(
+    controls: &AnvilControls,
+    payload: Vec<crate::Payload>,
+) -> eyre::Result<DeploymentDiff> {
+    let receipt = GateLock::deploy_builder(&controls.provider, contract_payloads(payload))
+        .send()
+        .await?
+        .get_receipt()
+        .await?;
+
+    let Some(address) = receipt.contract_address else {
+        bail!("deployment receipt {} has no contract address", receipt.transaction_hash)
+    };
+    let storage = trace_storage_diff(controls, receipt.transaction_hash).await?;
+
+    Ok(DeploymentDiff { address, tx_hash: receipt.transaction_hash, storage })
+}
+
+/// Collects the storage writes of a mined transaction using anvil's
+/// `debug_traceTransaction` with the prestate tracer in diff mode.
+pub async fn trace_storage_diff(
+    controls: &AnvilControls,
+    tx_hash: TxHash,
+) -> eyre::Result<StorageDiff> {
+    let options = GethDebugTracingOptions::default()
+        .with_tracer(GethDebugTracerType::BuiltInTracer(GethDebugBuiltInTracerType::PreStateTracer))
+        .with_prestate_config(PreStateConfig {
+            diff_mode: Some(true),
+            disable_code: Some(true),
+            ..Default::default()
+        });
+
+    let trace = controls
+        .provider
+        .debug_trace_transaction(tx_hash, options)
+        .await?;
+    let GethTrace::PreStateTracer(PreStateFrame::Diff(DiffMode { pre, post })) = trace else {
+        bail!("expected a prestate diff trace for {tx_hash}")
+    };
+
+    let mut diff = StorageDiff::new();
+    for address in pre
+        .keys()
+        .chain(post.keys())
+        .copied()
+        .collect::<BTreeSet<_>>()
+    {
+        let old = pre.get(&address).map(|acc| &acc.storage);
+        let new = post.get(&address).map(|acc| &acc.storage);
+
+        // Slots cleared to zero only show up in `pre`, and slots written from
+        // zero only show up in `post`.
+        let slots = old
+            .into_iter()
+            .chain(new)
+            .flat_map(|storage| storage.keys().copied())
+            .collect::<BTreeSet<_>>();
+
+        let writes = slots
+            .into_iter()
+            .map(|slot| SlotDiff {
+                slot,
+                old: old.and_then(|s| s.get(&slot)).copied().unwrap_or_default(),
+                new: new.and_then(|s| s.get(&slot)).copied().unwrap_or_default(),
+            })
+            .filter(|write| write.old != write.new)
+            .collect::<Vec<_>>();
+
+        if !writes.is_empty() {
+            diff.insert(address, writes);
+        }
+    }
+
+    Ok(diff)
 }
 
 pub struct AnvilControls {
     pub provider: AnvilProvider,
     pub wallet: EthereumWallet,
     pub instance: AnvilInstance,
+    pub code_index: CodeIndex,
 }
 
-impl DatabaseRef for AnvilControls {
-    type Error = eyre::Error;
+/// Bytecode seen by [`AnvilControls`], keyed by code hash, along with the
+/// account each hash was loaded from.
+#[derive(Debug, Default)]
+pub struct CodeIndex {
+    code: RwLock<HashMap<B256, Bytecode>>,
+    owners: RwLock<HashMap<B256, Address>>,
+}
 
-    fn basic_ref(
+impl CodeIndex {
+    pub fn insert(&self, address: Address, code_hash: B256, code: Bytecode) {
+        self.code
+            .write()
+            .expect("code index poisoned")
+            .insert(code_hash, code);
+        self.owners
+            .write()
+            .expect("code index poisoned")
+            .insert(code_hash, address);
+    }
+
+    /// Records `address` as holding code with `code_hash` without fetching
+    /// the code itself, so it can be loaded from there when first needed.
+    pub fn record_owner(&self, address: Address, code_hash: B256) {
+        if code_hash == KECCAK_EMPTY {
+            return;
+        }
+        self.owners
+            .write()
+            .expect("code index poisoned")
+            .insert(code_hash, address);
+    }
+
+    pub fn code(&self, code_hash: B256) -> Option<Bytecode> {
+        self.code
+            .read()
+            .expect("code index poisoned")
+            .get(&code_hash)
+            .cloned()
+    }
+
+    pub fn owner(&self, code_hash: B256) -> Option<Address> {
+        self.owners
+            .read()
+            .expect("code index poisoned")
+            .get(&code_hash)
+            .copied()
+    }
+}
+
+impl AnvilControls {
+    /// Returns a read-only view whose queries all target block `number`.
+    pub fn at_block(&self, number: u64) -> PinnedAnvilControls<'_> {
+        PinnedAnvilControls { controls: self, number }
+    }
+
+    /// Records the current chain state with `evm_snapshot`, returning an id
+    /// that [`revert`](Self::revert) can roll back to.
+    pub async fn snapshot(&self) -> eyre::Result<U256> {
+        Ok(self.provider.anvil_snapshot().await?)
+    }
+
+    /// Rolls the chain back to `snapshot` with `evm_revert`. Anvil drops the
+    /// snapshot once reverted to, so take a new one to revert there again.
+    pub async fn revert(&self, snapshot: U256) -> eyre::Result<()> {
+        if !self.provider.anvil_revert(snapshot).await? {
+            bail!("anvil has no snapshot {snapshot}");
+        }
+        Ok(())
+    }
+
+    /// Writes `overrides` to the node with `anvil_setStorageAt`,
+    /// `anvil_setBalance`, `anvil_setNonce` and `anvil_setCode`, so state
+    /// patched in an [`OverlayDb`](crate::overlay_db::OverlayDb) can be checked with real transactions.
+    /// Accounts are applied in address order and slots in slot order.
+    pub async fn apply_overrides(
+        &self,
+        overrides: &HashMap<Address, AccountOverride>,
+    ) -> eyre::Result<()> {
+        let overrides = overrides.iter().collect::<BTreeMap<_, _>>();
+
+        for (&address, account) in overrides {
+            if let Some(code) = &account.code {
+                self.provider
+                    .anvil_set_code(address, code.original_bytes())
+                    .await?;
+                self.code_index
+                    .insert(address, code.hash_slow(), code.clone());
+            }
+            if let Some(balance) = account.balance {
+                self.provider.anvil_set_balance(address, balance).await?;
+            }
+            if let Some(nonce) = account.nonce {
+                self.provider
+                    .anvil_set_nonce(address, U256::from(nonce))
+                    .await?;
+            }
+
+            let storage = account.storage.iter().collect::<BTreeMap<_, _>>();
+            for (&index, &value) in storage {
+                if !self
+                    .provider
+                    .anvil_set_storage_at(address, index, B256::from(value))
+                    .await?
+                {
+                    bail!("anvil refused to set storage slot {index} of {address}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs `call` against `to` with `eth_call` on top of `block`, with
+    /// `overrides` layered over the node's state. Nothing is written to the
+    /// chain.
+    pub async fn call_with_overrides<C: SolCall>(
+        &self,
+        to: Address,
+        call: &C,
+        block: BlockId,
+        overrides: &StateOverride,
+    ) -> eyre::Result<C::Return> {
+        let tx = TransactionRequest::default()
+            .with_to(to)
+            .with_input(call.abi_encode());
+        let output = self
+            .provider
+            .call(&tx)
+            .block(block)
+            .overrides(overrides)
+            .await?;
+
+        Ok(C::abi_decode_returns(&output, true)?)
+    }
+
+    /// Reads `slots` of `address` at `block` in a single JSON-RPC batch,
+    /// returning the values in the same order.
+    pub async fn storage_batch(
         &self,
         address: Address,
-    ) -> Result<Option<revm::primitives::AccountInfo>, Self::Error> {
-        let acc = async_to_sync(self.provider.get_account(address).latest().into_future())?;
-        let code = async_to_sync(self.provider.get_code_at(address).latest().into_future())?;
-        let code = Some(Bytecode::new_raw(code));
+        slots: &[U256],
+        block: BlockId,
+    ) -> Result<Vec<U256>, AnvilDbError> {
+        let Some(&first) = slots.first() else { return Ok(Vec::new()) };
+        let query = |index| Query::Storage { address, index, block };
+
+        let mut batch = BatchRequest::new(self.provider.client());
+        let waiters = slots
+            .iter()
+            .map(|&slot| {
+                let waiter = batch
+                    .add_call::<_, U256>("eth_getStorageAt", &(address, slot, block))
+                    .map_err(|e| AnvilDbError::transport(query(slot), e))?;
+                Ok((slot, waiter))
+            })
+            .collect::<Result<Vec<_>, AnvilDbError>>()?;
+
+        // A failed send is reported against the first slot of the batch.
+        batch
+            .send()
+            .await
+            .map_err(|e| AnvilDbError::transport(query(first), e))?;
+
+        let mut values = Vec::with_capacity(waiters.len());
+        for (slot, waiter) in waiters {
+            let value = waiter
+                .await
+                .map_err(|e| AnvilDbError::transport(query(slot), e))?;
+            values.push(value);
+        }
+
+        Ok(values)
+    }
+
+    /// State overrides setting `is_unlocked` on the `valueMap` entries of the
+    /// `GateLock` at `lock` keyed by `ids`, leaving the rest of each entry as
+    /// stored on the node at `block`. The entries are read in a single batch.
+    pub async fn unlock_overrides(
+        &self,
+        lock: Address,
+        ids: &[U256],
+        block: BlockId,
+    ) -> eyre::Result<StateOverride> {
+        if ids.is_empty() {
+            return Ok(StateOverride::default());
+        }
+
+        let slots = ids
+            .iter()
+            .map(|&id| storage::valueMap(id))
+            .collect::<Vec<_>>();
+        let words = self.storage_batch(lock, &slots, block).await?;
+
+        let state_diff = slots
+            .into_iter()
+            .zip(words)
+            .map(|(slot, word)| {
+                let values =
+                    storage::Values { is_unlocked: true, ..storage::Values::decode_packed(word) };
+                (B256::from(slot), B256::from(values.encode_packed()))
+            })
+            .collect();
+
+        let account = RpcAccountOverride { state_diff: Some(state_diff), ..Default::default() };
+        Ok(StateOverride::from_iter([(lock, account)]))
+    }
+
+    fn account_info_at(
+        &self,
+        address: Address,
+        block: BlockId,
+    ) -> Result<Option<revm::primitives::AccountInfo>, AnvilDbError> {
+        let acc = async_to_sync(
+            self.provider
+                .get_account(address)
+                .block_id(block)
+                .into_future(),
+        )
+        .map_err(|e| AnvilDbError::transport(Query::Account { address, block }, e))?;
+
+        if acc.nonce == 0 && acc.balance.is_zero() && acc.code_hash == KECCAK_EMPTY {
+            return Ok(None);
+        }
+
+        // The code is only fetched once REVM asks for it by hash, from the
+        // owner recorded here, unless it is already known.
+        self.code_index.record_owner(address, acc.code_hash);
 
         Ok(Some(revm::primitives::AccountInfo {
             code_hash: acc.code_hash,
             balance: acc.balance,
             nonce: acc.nonce,
-            code,
+            code: self.code_index.code(acc.code_hash),
         }))
     }
 
-    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
-        let acc = async_to_sync(self.provider.get_storage_at(address, index).into_future())?;
-        Ok(acc)
+    fn storage_at(
+        &self,
+        address: Address,
+        index: U256,
+        block: BlockId,
+    ) -> Result<U256, AnvilDbError> {
+        async_to_sync(
+            self.provider
+                .get_storage_at(address, index)
+                .block_id(block)
+                .into_future(),
+        )
+        .map_err(|e| AnvilDbError::transport(Query::Storage { address, index, block }, e))
     }
 
-    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+    fn block_hash_at(&self, number: u64) -> Result<B256, AnvilDbError> {
         let acc = async_to_sync(
             self.provider
                 .get_block_by_number(
@@ -97,17 +467,117 @@ impl DatabaseRef for AnvilControls {
                     alloy::rpc::types::BlockTransactionsKind::Hashes,
                 )
                 .into_future(),
-        )?;
+        )
+        .map_err(|e| AnvilDbError::transport(Query::BlockHash { number }, e))?;
 
-        let Some(block) = acc else { bail!("failed to load block") };
+        let Some(block) = acc else { return Err(AnvilDbError::MissingBlock { number }) };
         Ok(block.header.hash)
     }
 
-    fn code_by_hash_ref(&self, _: B256) -> Result<primitives::Bytecode, Self::Error> {
-        panic!("This should not be called, as the code is already loaded");
+    fn code_by_hash(&self, code_hash: B256, block: BlockId) -> Result<Bytecode, AnvilDbError> {
+        if code_hash == KECCAK_EMPTY {
+            return Ok(Bytecode::default());
+        }
+
+        if let Some(code) = self.code_index.code(code_hash) {
+            return Ok(code);
+        }
+
+        // The hash was seen on an account whose code was not fetched yet, so
+        // load it from that account and make sure it has not been replaced.
+        let Some(address) = self.code_index.owner(code_hash) else {
+            return Err(AnvilDbError::UnknownCodeHash { code_hash });
+        };
+        let code = async_to_sync(
+            self.provider
+                .get_code_at(address)
+                .block_id(block)
+                .into_future(),
+        )
+        .map_err(|e| AnvilDbError::transport(Query::Code { address, block }, e))?;
+        if code.is_empty() {
+            return Err(AnvilDbError::MissingAccount { address, block });
+        }
+        let code = Bytecode::new_raw(code);
+        if code.hash_slow() != code_hash {
+            return Err(AnvilDbError::CodeHashMismatch { address, code_hash });
+        }
+
+        self.code_index.insert(address, code_hash, code.clone());
+        Ok(code)
+    }
+}
+
+impl DatabaseRef for AnvilControls {
+    type Error = AnvilDbError;
+
+    fn basic_ref(
+        &self,
+        address: Address,
+    ) -> Result<Option<revm::primitives::AccountInfo>, Self::Error> {
+        self.account_info_at(address, BlockId::latest())
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.storage_at(address, index, BlockId::latest())
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        self.block_hash_at(number)
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<primitives::Bytecode, Self::Error> {
+        self.code_by_hash(code_hash, BlockId::latest())
+    }
+}
+
+/// Block-pinned view over [`AnvilControls`], giving REVM a consistent snapshot
+/// even if the node mines new blocks mid-simulation.
+#[derive(Clone, Copy)]
+pub struct PinnedAnvilControls<'a> {
+    controls: &'a AnvilControls,
+    number: u64,
+}
+
+impl PinnedAnvilControls<'_> {
+    pub fn block_number(&self) -> u64 {
+        self.number
+    }
+}
+
+impl DatabaseRef for PinnedAnvilControls<'_> {
+    type Error = AnvilDbError;
+
+    fn basic_ref(
+        &self,
+        address: Address,
+    ) -> Result<Option<revm::primitives::AccountInfo>, Self::Error> {
+        self.controls
+            .account_info_at(address, BlockId::number(self.number))
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.controls
+            .storage_at(address, index, BlockId::number(self.number))
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        if number > self.number {
+            return Err(AnvilDbError::MissingBlock { number });
+        }
+        self.controls.block_hash_at(number)
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<primitives::Bytecode, Self::Error> {
+        self.controls
+            .code_by_hash(code_hash, BlockId::number(self.number))
     }
 }
 
+/// Runs `f` to completion from sync code on the current tokio runtime.
+///
+/// This needs a multi-threaded runtime; use
+/// [`AnvilBackgroundDb`](crate::background_db::AnvilBackgroundDb) elsewhere.
 pub fn async_to_sync<F: Future>(f: F) -> F::Output {
     let handle = tokio::runtime::Handle::try_current().expect("No tokio runtime found");
     tokio::task::block_in_place(|| handle.block_on(f))
@@ -0,0 +1,103 @@
+use std::{fmt, time::Duration};
+
+use alloy::{
+    eips::BlockId,
+    primitives::{Address, B256, U256},
+    rpc::json_rpc::RpcError,
+    transports::TransportError,
+};
+
+/// The node query an [`AnvilDbError`] was raised for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    Account { address: Address, block: BlockId },
+    Storage { address: Address, index: U256, block: BlockId },
+    Code { address: Address, block: BlockId },
+    BlockHash { number: u64 },
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::Account { address, block } => write!(f, "account {address} at {block}"),
+            Query::Storage { address, index, block } => {
+                write!(f, "storage slot {index} of {address} at {block}")
+            }
+            Query::Code { address, block } => write!(f, "code of {address} at {block}"),
+            Query::BlockHash { number } => write!(f, "hash of block {number}"),
+        }
+    }
+}
+
+/// Errors raised by the anvil-backed [`revm::DatabaseRef`] implementations.
+#[derive(Debug)]
+pub enum AnvilDbError {
+    /// The request could not be delivered or the node answered with an error.
+    /// The source is boxed to keep `Result<_, AnvilDbError>` small.
+    Transport { query: Query, source: Box<TransportError> },
+    /// The node answered but the response could not be (de)serialized.
+    Decoding { query: Query, source: Box<TransportError> },
+    /// The node does not know the requested block, or it lies past the block
+    /// the database is pinned to.
+    MissingBlock { number: u64 },
+    /// The query needs `address` to exist at `block`, but it is empty there.
+    MissingAccount { address: Address, block: BlockId },
+    /// `address` was indexed as holding `code_hash` but now holds other code.
+    CodeHashMismatch { address: Address, code_hash: B256 },
+    /// No account seen so far has code with this hash.
+    UnknownCodeHash { code_hash: B256 },
+    /// The node did not answer within the configured timeout.
+    Timeout { query: Query, after: Duration },
+    /// The thread serving requests has shut down.
+    Disconnected,
+}
+
+impl AnvilDbError {
+    /// Classifies a transport error raised while running `query`.
+    pub fn transport(query: Query, source: TransportError) -> Self {
+        let source = Box::new(source);
+        match *source {
+            RpcError::SerError(_) | RpcError::DeserError { .. } => Self::Decoding { query, source },
+            _ => Self::Transport { query, source },
+        }
+    }
+
+    /// Returns whether retrying the same query might succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Transport { source, .. } => matches!(**source, RpcError::Transport(_)),
+            Self::Timeout { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for AnvilDbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport { query, source } => write!(f, "failed to load {query}: {source}"),
+            Self::Decoding { query, source } => write!(f, "failed to decode {query}: {source}"),
+            Self::MissingBlock { number } => write!(f, "block {number} is not available"),
+            Self::MissingAccount { address, block } => {
+                write!(f, "account {address} does not exist at {block}")
+            }
+            Self::CodeHashMismatch { address, code_hash } => {
+                write!(f, "{address} no longer holds code with hash {code_hash}")
+            }
+            Self::UnknownCodeHash { code_hash } => write!(f, "unknown code hash {code_hash}"),
+            Self::Timeout { query, after } => {
+                write!(f, "timed out after {after:?} loading {query}")
+            }
+            Self::Disconnected => write!(f, "anvil database thread has stopped"),
+        }
+    }
+}
+
+impl std::error::Error for AnvilDbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport { source, .. } | Self::Decoding { source, .. } => Some(&**source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::future::Future;
+
+use alloy::{
+    primitives::{Address, Bytes, address},
+    sol_types::SolConstructor,
+};
+use eyre::bail;
+use revm::{
+    Evm,
+    db::{CacheDB, EmptyDB},
+    primitives::{ExecutionResult, Output, TransactTo},
+};
+
+use crate::{
+    Payload, backend::InMemoryBackend, contract_bindings::gate_lock_sol::gate_lock::GateLock,
+    deploy_setup_with_backend, environment_deployment::contract_payloads,
+};
+
+/// In-process database used instead of an anvil node.
+pub type InMemoryDb = CacheDB<EmptyDB>;
+
+/// Sender used for in-memory deployments.
+pub const IN_MEMORY_DEPLOYER: Address = address!("00000000000000000000000000000000000d3910");
+
+/// Runs `init_code` as a creation transaction against `db` and commits the
+/// result, returning the address of the new contract.
+pub fn deploy_bytecode_in_memory(db: &mut InMemoryDb, init_code: Bytes) -> eyre::Result<Address> {
+    let mut evm = Evm::builder()
+        .with_db(db)
+        .modify_tx_env(|tx| {
+            tx.caller = IN_MEMORY_DEPLOYER;
+            tx.transact_to = TransactTo::Create;
+            tx.data = init_code;
+            tx.gas_limit = 30_000_000;
+        })
+        .build();
+
+    let result = evm
+        .transact_commit()
+        .map_err(|e| eyre::eyre!("REVM deployment failed: {:?}", e))?;
+
+    match result {
+        ExecutionResult::Success { output: Output::Create(_, Some(address)), .. } => Ok(address),
+        result => bail!("deployment did not create a contract: {:?}", result),
+    }
+}
+
+/// Deploys `GateLock` into a fresh in-memory database, mirroring
+/// [`deploy_lock_contract`](crate::environment_deployment::deploy_lock_contract)
+/// without an anvil process.
+pub fn deploy_lock_contract_in_memory(
+    payload: Vec<Payload>,
+) -> eyre::Result<(Address, InMemoryDb)> {
+    let mut init_code = GateLock::BYTECODE.to_vec();
+    init_code
+        .extend(GateLock::constructorCall { initPayload: contract_payloads(payload) }.abi_encode());
+
+    let mut db = InMemoryDb::new(EmptyDB::default());
+    let address = deploy_bytecode_in_memory(&mut db, init_code.into())?;
+
+    Ok((address, db))
+}
+
+/// Same as [`deploy_setup_with_solver`](crate::deploy_setup_with_solver) but
+/// deploys into an in-memory REVM database instead of spawning anvil.
+pub async fn deploy_setup_in_memory_with_solver<F, O>(f: F) -> eyre::Result<bool>
+where
+    F: Fn(Address, InMemoryDb) -> O,
+    O: Future<Output = eyre::Result<bool>>,
+{
+    deploy_setup_with_backend(&InMemoryBackend, f).await
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{U160, U256};
+    use revm::DatabaseRef;
+
+    use super::*;
+    use crate::contract_bindings::gate_lock_sol::gate_lock::storage::{self, Values};
+
+    #[test]
+    fn deploys_payload_into_storage() {
+        let payload = vec![
+            Payload { firstValue: 100, secondValue: U160::from(0x123) },
+            Payload { firstValue: 201, secondValue: U160::from(0x456) },
+        ];
+        let (address, db) = deploy_lock_contract_in_memory(payload).unwrap();
+
+        assert_eq!(db.storage_ref(address, storage::TOTAL_LENGTH.slot).unwrap(), U256::from(2));
+        assert_eq!(
+            Values::decode_packed(
+                db.storage_ref(address, storage::valueMap(U256::ZERO))
+                    .unwrap()
+            ),
+            Values { firstValue: 100, secondValue: U160::from(0x123), is_unlocked: false }
+        );
+        // An even `firstValue` keys the next entry.
+        assert_eq!(
+            Values::decode_packed(
+                db.storage_ref(address, storage::valueMap(U256::from(100)))
+                    .unwrap()
+            ),
+            Values { firstValue: 201, secondValue: U160::from(0x456), is_unlocked: false }
+        );
+    }
+}
@@ -1,24 +1,106 @@
+pub mod anvil_cache;
+pub mod anvil_config;
+pub mod backend;
+pub mod background_db;
 pub mod contract_bindings;
+pub mod differential;
 pub mod environment_deployment;
+pub mod error;
+pub mod in_memory;
+pub mod overlay_db;
+pub mod payload_generators;
+pub mod revm_env;
+pub mod storage_layout;
+pub mod stress;
 
 use std::fmt::Debug;
 use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, fs, process};
 
 use alloy::primitives::{Address, U160, U256};
-use environment_deployment::{AnvilControls, deploy_lock_contract, spin_up_anvil_instance};
-use rand::{self, Rng};
+use backend::{AnvilBackend, SolverBackend};
+use environment_deployment::AnvilControls;
+use payload_generators::PayloadGenerator;
+use rand::{self, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use revm::DatabaseRef;
+use serde::{Deserialize, Serialize};
+
+/// Replays a run from a saved seed when set.
+pub const SEED_ENV: &str = "GATELOCK_SEED";
+/// Replays a run from a saved JSON payload file when set.
+pub const PAYLOAD_FILE_ENV: &str = "GATELOCK_PAYLOAD_FILE";
 
 /// Payload structure matching the Solidity contract
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Payload {
     pub firstValue: u64,
     pub secondValue: U160,
 }
 
+/// Where the payloads of a run come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadSource {
+    /// Random payloads from a freshly drawn seed.
+    Random,
+    /// Random payloads replayed from a seed logged by an earlier run.
+    Seed(u64),
+    /// These exact payloads.
+    Payload(Vec<Payload>),
+    /// Payloads loaded from a JSON file written by [`save_payload`].
+    File(PathBuf),
+    /// Payloads of the given shape, derived from a seed.
+    Generated(PayloadGenerator, u64),
+}
+
+impl PayloadSource {
+    /// Reads [`PAYLOAD_FILE_ENV`] then [`SEED_ENV`], falling back to
+    /// [`PayloadSource::Random`].
+    pub fn from_env() -> eyre::Result<Self> {
+        if let Ok(path) = env::var(PAYLOAD_FILE_ENV) {
+            return Ok(Self::File(path.into()));
+        }
+
+        match env::var(SEED_ENV) {
+            Ok(seed) => Ok(Self::Seed(seed.parse()?)),
+            Err(_) => Ok(Self::Random),
+        }
+    }
+
+    /// Produces the payloads, logging how to replay them.
+    pub fn resolve(self) -> eyre::Result<Vec<Payload>> {
+        match self {
+            Self::Random => Ok(fetch_values()),
+            Self::Seed(seed) => {
+                println!("Using payload seed {seed} (replay with {SEED_ENV}={seed})");
+                Ok(fetch_values_seeded(seed))
+            }
+            Self::Payload(payload) => Ok(payload),
+            Self::File(path) => {
+                println!("Loading payloads from {}", path.display());
+                load_payload(&path)
+            }
+            Self::Generated(generator, seed) => {
+                println!("Using {} payloads with seed {seed}", generator.name());
+                Ok(generator.generate(seed))
+            }
+        }
+    }
+}
+
 /// generates values for smart_contract
 fn fetch_values() -> Vec<Payload> {
-    let mut rng = rand::rng();
+    let seed = rand::rng().random();
+    println!("Using payload seed {seed} (replay with {SEED_ENV}={seed})");
+    fetch_values_seeded(seed)
+}
+
+/// generates the same values for smart_contract every time `seed` is reused,
+/// on any platform and `rand` version
+pub fn fetch_values_seeded(seed: u64) -> Vec<Payload> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
     let iter_cnt: usize = rng.random_range(10..100);
 
     (0..iter_cnt)
@@ -29,15 +111,92 @@ fn fetch_values() -> Vec<Payload> {
         .collect::<Vec<_>>()
 }
 
+pub fn save_payload(path: impl AsRef<Path>, payload: &[Payload]) -> eyre::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(payload)?)?;
+    Ok(())
+}
+
+/// A fresh path in the temp directory, so concurrent runs do not overwrite
+/// each other's failing payloads.
+fn failing_payload_path() -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    env::temp_dir().join(format!("gatelock-failing-payload-{}-{nanos}.json", process::id()))
+}
+
+pub fn load_payload(path: impl AsRef<Path>) -> eyre::Result<Vec<Payload>> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
 pub async fn deploy_setup_with_solver<F, O>(f: F) -> eyre::Result<bool>
 where
     F: Fn(Address, AnvilControls) -> O,
     O: Future<Output = eyre::Result<bool>>,
 {
-    let controls = spin_up_anvil_instance().await?;
-    let payload = fetch_values();
+    deploy_setup_with_backend(&AnvilBackend, f).await
+}
+
+/// Same as [`deploy_setup_with_solver`] with payloads derived from `seed`.
+pub async fn deploy_setup_with_seed<F, O>(seed: u64, f: F) -> eyre::Result<bool>
+where
+    F: Fn(Address, AnvilControls) -> O,
+    O: Future<Output = eyre::Result<bool>>,
+{
+    deploy_setup_from_source(&AnvilBackend, PayloadSource::Seed(seed), f).await
+}
+
+/// Same as [`deploy_setup_with_solver`] with an explicit payload.
+pub async fn deploy_setup_with_payload<F, O>(payload: Vec<Payload>, f: F) -> eyre::Result<bool>
+where
+    F: Fn(Address, AnvilControls) -> O,
+    O: Future<Output = eyre::Result<bool>>,
+{
+    deploy_setup_from_source(&AnvilBackend, PayloadSource::Payload(payload), f).await
+}
+
+/// Same as [`deploy_setup_with_solver`], deploying on `backend` instead of a
+/// fresh anvil instance.
+pub async fn deploy_setup_with_backend<B, F, O>(backend: &B, f: F) -> eyre::Result<bool>
+where
+    B: SolverBackend,
+    F: Fn(Address, B::Db) -> O,
+    O: Future<Output = eyre::Result<bool>>,
+{
+    deploy_setup_from_source(backend, PayloadSource::from_env()?, f).await
+}
+
+/// Deploys payloads from `source` on `backend` and runs the solver. When the
+/// solver does not succeed the payload is saved so the run can be replayed
+/// through [`PAYLOAD_FILE_ENV`].
+pub async fn deploy_setup_from_source<B, F, O>(
+    backend: &B,
+    source: PayloadSource,
+    f: F,
+) -> eyre::Result<bool>
+where
+    B: SolverBackend,
+    F: Fn(Address, B::Db) -> O,
+    O: Future<Output = eyre::Result<bool>>,
+{
+    let payload = source.resolve()?;
+
+    let (deploy_address, db) = backend.deploy(payload.clone()).await?;
 
-    let deploy_address = deploy_lock_contract(&controls, payload).await?;
+    let result = f(deploy_address, db).await;
+    if !matches!(result, Ok(true)) {
+        let path = failing_payload_path();
+        match save_payload(&path, &payload) {
+            Ok(()) => {
+                println!(
+                    "Saved failing payload (replay with {PAYLOAD_FILE_ENV}={})",
+                    path.display()
+                );
+            }
+            Err(e) => eprintln!("Could not save failing payload to {}: {e:#}", path.display()),
+        }
+    }
 
-    f(deploy_address, controls).await
+    result
 }
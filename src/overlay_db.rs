@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use alloy::{
+    primitives::{Address, B256, U256},
+    rpc::types::state::{AccountOverride as RpcAccountOverride, StateOverride},
+};
+use revm::{
+    Database, DatabaseRef,
+    primitives::{AccountInfo, Bytecode},
+};
+
+use crate::environment_deployment::AnvilControls;
+
+/// Per-account values layered over the inner database by [`OverlayDb`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountOverride {
+    pub balance: Option<U256>,
+    pub nonce: Option<u64>,
+    pub code: Option<Bytecode>,
+    pub storage: HashMap<U256, U256>,
+}
+
+impl AccountOverride {
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_none()
+            && self.nonce.is_none()
+            && self.code.is_none()
+            && self.storage.is_empty()
+    }
+}
+
+/// In-memory overrides of storage, balances, nonces and bytecode layered over
+/// any [`DatabaseRef`]. Reads fall through to the inner database for
+/// everything that has not been overridden, and its errors are passed on
+/// untouched.
+#[derive(Debug, Clone)]
+pub struct OverlayDb<DB> {
+    inner: DB,
+    overrides: HashMap<Address, AccountOverride>,
+    code_by_hash: HashMap<B256, Bytecode>,
+}
+
+impl<DB> OverlayDb<DB> {
+    pub fn new(inner: DB) -> Self {
+        Self { inner, overrides: HashMap::new(), code_by_hash: HashMap::new() }
+    }
+
+    pub fn inner(&self) -> &DB {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> DB {
+        self.inner
+    }
+
+    pub fn overrides(&self) -> &HashMap<Address, AccountOverride> {
+        &self.overrides
+    }
+
+    pub fn account_override(&self, address: Address) -> Option<&AccountOverride> {
+        self.overrides.get(&address)
+    }
+
+    pub fn set_storage(&mut self, address: Address, index: U256, value: U256) {
+        self.overrides
+            .entry(address)
+            .or_default()
+            .storage
+            .insert(index, value);
+    }
+
+    pub fn set_balance(&mut self, address: Address, balance: U256) {
+        self.overrides.entry(address).or_default().balance = Some(balance);
+    }
+
+    pub fn set_nonce(&mut self, address: Address, nonce: u64) {
+        self.overrides.entry(address).or_default().nonce = Some(nonce);
+    }
+
+    pub fn set_code(&mut self, address: Address, code: Bytecode) {
+        self.code_by_hash.insert(code.hash_slow(), code.clone());
+        self.overrides.entry(address).or_default().code = Some(code);
+    }
+
+    pub fn remove_storage(&mut self, address: Address, index: U256) -> Option<U256> {
+        self.update(address, |account| account.storage.remove(&index))
+    }
+
+    pub fn remove_balance(&mut self, address: Address) -> Option<U256> {
+        self.update(address, |account| account.balance.take())
+    }
+
+    pub fn remove_nonce(&mut self, address: Address) -> Option<u64> {
+        self.update(address, |account| account.nonce.take())
+    }
+
+    pub fn remove_code(&mut self, address: Address) -> Option<Bytecode> {
+        self.update(address, |account| account.code.take())
+    }
+
+    /// Drops every override of `address`.
+    pub fn remove_account(&mut self, address: Address) -> Option<AccountOverride> {
+        self.overrides.remove(&address)
+    }
+
+    /// The overrides as `eth_call` state overrides, so a node sees the same
+    /// state REVM does. Storage goes in `stateDiff`, leaving slots that were
+    /// not overridden untouched.
+    pub fn state_override(&self) -> StateOverride {
+        self.overrides
+            .iter()
+            .map(|(&address, account)| {
+                let state_diff = (!account.storage.is_empty()).then(|| {
+                    account
+                        .storage
+                        .iter()
+                        .map(|(&index, &value)| (B256::from(index), B256::from(value)))
+                        .collect()
+                });
+
+                let account = RpcAccountOverride {
+                    balance: account.balance,
+                    nonce: account.nonce,
+                    code: account.code.as_ref().map(Bytecode::original_bytes),
+                    state_diff,
+                    ..Default::default()
+                };
+                (address, account)
+            })
+            .collect()
+    }
+
+    /// Persists every override to the anvil node behind `controls`.
+    pub async fn apply_to(&self, controls: &AnvilControls) -> eyre::Result<()> {
+        controls.apply_overrides(&self.overrides).await
+    }
+
+    /// Drops every override.
+    pub fn clear(&mut self) {
+        self.overrides.clear();
+        self.code_by_hash.clear();
+    }
+
+    /// Applies `f` to the overrides of `address`, forgetting the account once
+    /// nothing is left overridden.
+    fn update<T>(
+        &mut self,
+        address: Address,
+        f: impl FnOnce(&mut AccountOverride) -> Option<T>,
+    ) -> Option<T> {
+        let account = self.overrides.get_mut(&address)?;
+        let out = f(account);
+        if account.is_empty() {
+            self.overrides.remove(&address);
+        }
+        out
+    }
+}
+
+impl<DB: DatabaseRef> DatabaseRef for OverlayDb<DB> {
+    type Error = DB::Error;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let info = self.inner.basic_ref(address)?;
+        let Some(account) = self.overrides.get(&address) else { return Ok(info) };
+
+        let mut info = info.unwrap_or_default();
+        if let Some(balance) = account.balance {
+            info.balance = balance;
+        }
+        if let Some(nonce) = account.nonce {
+            info.nonce = nonce;
+        }
+        if let Some(code) = &account.code {
+            info.code_hash = code.hash_slow();
+            info.code = Some(code.clone());
+        }
+
+        Ok(Some(info))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        match self.code_by_hash.get(&code_hash) {
+            Some(code) => Ok(code.clone()),
+            None => self.inner.code_by_hash_ref(code_hash),
+        }
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        match self
+            .overrides
+            .get(&address)
+            .and_then(|account| account.storage.get(&index))
+        {
+            Some(value) => Ok(*value),
+            None => self.inner.storage_ref(address, index),
+        }
+    }
+
+    fn block_hash_ref(&self, number: u64) -> Result<B256, Self::Error> {
+        self.inner.block_hash_ref(number)
+    }
+}
+
+impl<DB: DatabaseRef> Database for OverlayDb<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.basic_ref(address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.code_by_hash_ref(code_hash)
+    }
+
+    fn storage(&mut self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.storage_ref(address, index)
+    }
+
+    fn block_hash(&mut self, number: u64) -> Result<B256, Self::Error> {
+        self.block_hash_ref(number)
+    }
+}
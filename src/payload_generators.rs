@@ -0,0 +1,278 @@
+use alloy::primitives::{U160, U256};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{Payload, fetch_values_seeded};
+
+/// Longest payload [`fetch_values_seeded`] produces.
+pub const MAX_PAYLOAD_LEN: usize = 99;
+
+/// Named payload shapes exercising `GateLock` edge cases that random payloads
+/// almost never hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PayloadGenerator {
+    /// Same distribution as [`fetch_values_seeded`].
+    Random,
+    /// The slot chain jumps back to an earlier key and loops, so `valueMap`
+    /// entries get overwritten and fewer distinct keys exist than
+    /// `totalLength`.
+    Cycle,
+    /// The chain settles on a key that points back at itself.
+    SelfLoop,
+    /// Some entries have `firstValue == 0`, sending the chain back to key 0.
+    ZeroFirstValue,
+    /// Even `firstValue`s whose `secondValue` holds the same number, so both
+    /// branches of the constructor lead to the same key.
+    Collision,
+    /// The longest payload with extreme values.
+    MaxLength,
+    /// No entries at all.
+    Empty,
+}
+
+impl PayloadGenerator {
+    pub const ALL: [Self; 7] = [
+        Self::Random,
+        Self::Cycle,
+        Self::SelfLoop,
+        Self::ZeroFirstValue,
+        Self::Collision,
+        Self::MaxLength,
+        Self::Empty,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Random => "random",
+            Self::Cycle => "cycle",
+            Self::SelfLoop => "self-loop",
+            Self::ZeroFirstValue => "zero-first-value",
+            Self::Collision => "collision",
+            Self::MaxLength => "max-length",
+            Self::Empty => "empty",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|generator| generator.name() == name)
+    }
+
+    /// Generates a payload of this shape, deterministic in `seed`.
+    pub fn generate(&self, seed: u64) -> Vec<Payload> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+        match self {
+            Self::Random => fetch_values_seeded(seed),
+            Self::Cycle => {
+                let len = rng.random_range(10..=MAX_PAYLOAD_LEN);
+                let distinct = rng.random_range(2..len);
+                let loop_start = rng.random_range(0..distinct);
+
+                let mut keys = vec![U256::ZERO];
+                keys.extend((1..distinct).map(|_| random_key(&mut rng)));
+                let keys = (0..len)
+                    .map(|i| {
+                        if i < distinct {
+                            keys[i]
+                        } else {
+                            keys[loop_start + (i - distinct) % (distinct - loop_start)]
+                        }
+                    })
+                    .collect::<Vec<_>>();
+
+                chain(&keys, &mut rng)
+            }
+            Self::SelfLoop => {
+                let len = rng.random_range(10..=MAX_PAYLOAD_LEN);
+                let distinct = rng.random_range(1..len);
+
+                let mut keys = vec![U256::ZERO];
+                keys.extend((1..distinct).map(|_| random_key(&mut rng)));
+                keys.resize(len, keys[distinct - 1]);
+
+                chain(&keys, &mut rng)
+            }
+            Self::ZeroFirstValue => {
+                let len = rng.random_range(10..=MAX_PAYLOAD_LEN);
+                (0..len)
+                    .map(|_| {
+                        let first_value = if rng.random_ratio(1, 3) { 0 } else { rng.random() };
+                        Payload { firstValue: first_value, secondValue: random_key_u160(&mut rng) }
+                    })
+                    .collect()
+            }
+            Self::Collision => {
+                let len = rng.random_range(10..=MAX_PAYLOAD_LEN);
+                (0..len)
+                    .map(|_| {
+                        let first_value = rng.random::<u64>() & !1;
+                        Payload { firstValue: first_value, secondValue: U160::from(first_value) }
+                    })
+                    .collect()
+            }
+            Self::MaxLength => (0..MAX_PAYLOAD_LEN)
+                .map(|i| Payload {
+                    firstValue: if i % 2 == 0 { u64::MAX } else { u64::MAX - 1 },
+                    secondValue: if rng.random() { U160::MAX } else { random_key_u160(&mut rng) },
+                })
+                .collect(),
+            Self::Empty => Vec::new(),
+        }
+    }
+}
+
+/// Replays the constructor, returning the `valueMap` key written by each
+/// payload entry in order.
+pub fn written_keys(payload: &[Payload]) -> Vec<U256> {
+    let mut slot = U256::ZERO;
+    payload
+        .iter()
+        .map(|cur| {
+            let written = slot;
+            slot = if cur.firstValue % 2 == 0 {
+                U256::from(cur.firstValue)
+            } else {
+                U256::from(cur.secondValue)
+            };
+            written
+        })
+        .collect()
+}
+
+/// Builds a payload whose entry `i` is written at `keys[i]` and sends the
+/// constructor on to `keys[i + 1]`. `keys[0]` must be zero, and every key must
+/// fit in a `uint160`.
+fn chain(keys: &[U256], rng: &mut ChaCha8Rng) -> Vec<Payload> {
+    debug_assert_eq!(keys.first(), Some(&U256::ZERO));
+
+    (0..keys.len())
+        .map(|i| match keys.get(i + 1) {
+            Some(&next) => jump_to(next, rng),
+            None => Payload { firstValue: rng.random(), secondValue: random_key_u160(rng) },
+        })
+        .collect()
+}
+
+/// A payload entry whose successor key is `next`, taking the even
+/// `firstValue` branch when `next` allows it and the coin says so.
+fn jump_to(next: U256, rng: &mut ChaCha8Rng) -> Payload {
+    match u64::try_from(next) {
+        Ok(next) if next % 2 == 0 && rng.random() => {
+            Payload { firstValue: next, secondValue: random_key_u160(rng) }
+        }
+        _ => Payload { firstValue: rng.random::<u64>() | 1, secondValue: U160::from(next) },
+    }
+}
+
+fn random_key_u160(rng: &mut ChaCha8Rng) -> U160 {
+    U160::from_be_bytes(rng.random::<[u8; 20]>())
+}
+
+fn random_key(rng: &mut ChaCha8Rng) -> U256 {
+    U256::from(random_key_u160(rng))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    const SEEDS: std::ops::Range<u64> = 0..32;
+
+    #[test]
+    fn names_round_trip() {
+        for generator in PayloadGenerator::ALL {
+            assert_eq!(PayloadGenerator::from_name(generator.name()), Some(generator));
+        }
+        assert_eq!(PayloadGenerator::from_name("unknown"), None);
+    }
+
+    #[test]
+    fn generators_are_deterministic() {
+        for generator in PayloadGenerator::ALL {
+            for seed in SEEDS {
+                assert_eq!(
+                    generator.generate(seed),
+                    generator.generate(seed),
+                    "{}",
+                    generator.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn random_matches_fetch_values_seeded() {
+        for seed in SEEDS {
+            assert_eq!(PayloadGenerator::Random.generate(seed), fetch_values_seeded(seed));
+        }
+    }
+
+    #[test]
+    fn cycle_revisits_keys() {
+        for seed in SEEDS {
+            let payload = PayloadGenerator::Cycle.generate(seed);
+            let keys = written_keys(&payload);
+            let distinct = keys.iter().collect::<HashSet<_>>().len();
+            assert!(distinct < payload.len(), "seed {seed}: {distinct} of {}", payload.len());
+        }
+    }
+
+    #[test]
+    fn self_loop_repeats_final_key() {
+        for seed in SEEDS {
+            let keys = written_keys(&PayloadGenerator::SelfLoop.generate(seed));
+            let [.., before_last, last] = keys.as_slice() else {
+                panic!("seed {seed}: payload too short");
+            };
+            assert_eq!(before_last, last, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn zero_first_value_sends_chain_to_zero() {
+        let zeros = SEEDS
+            .flat_map(|seed| PayloadGenerator::ZeroFirstValue.generate(seed))
+            .filter(|entry| entry.firstValue == 0)
+            .count();
+        assert!(zeros > 0);
+    }
+
+    #[test]
+    fn collision_branches_agree() {
+        for seed in SEEDS {
+            for entry in PayloadGenerator::Collision.generate(seed) {
+                assert_eq!(entry.firstValue % 2, 0, "seed {seed}");
+                assert_eq!(U160::from(entry.firstValue), entry.secondValue, "seed {seed}");
+            }
+        }
+    }
+
+    #[test]
+    fn max_length_is_longest() {
+        for seed in SEEDS {
+            assert_eq!(PayloadGenerator::MaxLength.generate(seed).len(), MAX_PAYLOAD_LEN);
+            assert!(fetch_values_seeded(seed).len() <= MAX_PAYLOAD_LEN);
+        }
+    }
+
+    #[test]
+    fn empty_is_empty() {
+        for seed in SEEDS {
+            assert!(PayloadGenerator::Empty.generate(seed).is_empty());
+        }
+    }
+
+    #[test]
+    fn written_keys_follows_branches() {
+        let payload = [
+            Payload { firstValue: 4, secondValue: U160::from(9) },
+            Payload { firstValue: 3, secondValue: U160::from(9) },
+            Payload { firstValue: 0, secondValue: U160::from(1) },
+        ];
+        assert_eq!(written_keys(&payload), [U256::ZERO, U256::from(4), U256::from(9)]);
+    }
+}
@@ -0,0 +1,99 @@
+use alloy::{
+    consensus::Header,
+    network::Ethereum,
+    providers::Provider,
+    rpc::types::{BlockNumberOrTag, BlockTransactionsKind},
+    transports::Transport,
+};
+use revm::primitives::{
+    BlobExcessGasAndPrice, BlockEnv, CfgEnv, Env, EnvWithHandlerCfg, SpecId, U256,
+};
+
+use crate::environment_deployment::AnvilControls;
+
+/// Guesses the hardfork a block was built under from the header fields each
+/// fork introduced.
+pub fn spec_id_from_header(header: &Header) -> SpecId {
+    if header.requests_hash.is_some() {
+        SpecId::PRAGUE
+    } else if header.parent_beacon_block_root.is_some() || header.excess_blob_gas.is_some() {
+        SpecId::CANCUN
+    } else if header.withdrawals_root.is_some() {
+        SpecId::SHANGHAI
+    } else if header.base_fee_per_gas.is_some() && header.difficulty.is_zero() {
+        SpecId::MERGE
+    } else if header.base_fee_per_gas.is_some() {
+        SpecId::LONDON
+    } else {
+        SpecId::BERLIN
+    }
+}
+
+/// Block environment for executing on top of `header`, the way the node
+/// would have executed its transactions.
+pub fn block_env_from_header(header: &Header, spec_id: SpecId) -> BlockEnv {
+    BlockEnv {
+        number: U256::from(header.number),
+        coinbase: header.beneficiary,
+        timestamp: U256::from(header.timestamp),
+        gas_limit: U256::from(header.gas_limit),
+        basefee: U256::from(header.base_fee_per_gas.unwrap_or_default()),
+        difficulty: header.difficulty,
+        prevrandao: spec_id
+            .is_enabled_in(SpecId::MERGE)
+            .then_some(header.mix_hash),
+        blob_excess_gas_and_price: header.excess_blob_gas.map(|excess| {
+            BlobExcessGasAndPrice::new(excess, spec_id.is_enabled_in(SpecId::PRAGUE))
+        }),
+    }
+}
+
+/// Full REVM environment matching `header` on chain `chain_id`. The
+/// transaction is left at its defaults for the caller to fill in.
+pub fn env_from_header(header: &Header, chain_id: u64) -> EnvWithHandlerCfg {
+    let spec_id = spec_id_from_header(header);
+
+    let mut cfg = CfgEnv::default();
+    cfg.chain_id = chain_id;
+
+    let env = Env { cfg, block: block_env_from_header(header, spec_id), ..Env::default() };
+    EnvWithHandlerCfg::new_with_spec_id(Box::new(env), spec_id)
+}
+
+/// Fetches block `block` and the chain id from `provider` and builds the
+/// matching REVM environment.
+pub async fn env_at<P, T>(provider: &P, block: BlockNumberOrTag) -> eyre::Result<EnvWithHandlerCfg>
+where
+    P: Provider<T, Ethereum>,
+    T: Transport + Clone,
+{
+    let chain_id = provider.get_chain_id().await?;
+    let fetched = provider
+        .get_block_by_number(block, BlockTransactionsKind::Hashes)
+        .await?
+        .ok_or_else(|| eyre::eyre!("node has no block {block}"))?;
+
+    Ok(env_from_header(&fetched.header.inner, chain_id))
+}
+
+/// Fetches the latest block and chain id from `provider` and builds the
+/// matching REVM environment.
+pub async fn latest_env<P, T>(provider: &P) -> eyre::Result<EnvWithHandlerCfg>
+where
+    P: Provider<T, Ethereum>,
+    T: Transport + Clone,
+{
+    env_at(provider, BlockNumberOrTag::Latest).await
+}
+
+impl AnvilControls {
+    /// REVM environment matching the node's latest block.
+    pub async fn revm_env(&self) -> eyre::Result<EnvWithHandlerCfg> {
+        latest_env(&self.provider).await
+    }
+
+    /// REVM environment matching block `number`.
+    pub async fn revm_env_at(&self, number: u64) -> eyre::Result<EnvWithHandlerCfg> {
+        env_at(&self.provider, BlockNumberOrTag::Number(number)).await
+    }
+}
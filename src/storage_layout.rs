@@ -0,0 +1,255 @@
+use alloy::primitives::{Address, B256, FixedBytes, U256, Uint, keccak256};
+
+/// A storage slot index.
+pub type Slot = U256;
+
+/// A state variable as laid out in contract storage, generated from the
+/// `storageLayout` output of the compiler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageVariable {
+    pub label: &'static str,
+    pub slot: Slot,
+    /// Byte offset of the variable within its slot, counted from the least
+    /// significant byte.
+    pub offset: u8,
+    pub type_label: &'static str,
+}
+
+/// Looks up a state variable in a generated layout by its Solidity name.
+pub fn find(layout: &'static [StorageVariable], label: &str) -> Option<&'static StorageVariable> {
+    layout.iter().find(|variable| variable.label == label)
+}
+
+/// A value type usable as a mapping key, encoded as its 32 byte ABI word.
+pub trait MappingKey {
+    fn key_word(&self) -> B256;
+}
+
+impl MappingKey for U256 {
+    fn key_word(&self) -> B256 {
+        B256::from(*self)
+    }
+}
+
+impl MappingKey for Address {
+    fn key_word(&self) -> B256 {
+        self.into_word()
+    }
+}
+
+impl MappingKey for B256 {
+    fn key_word(&self) -> B256 {
+        *self
+    }
+}
+
+/// Slot of `mapping[key]` for a mapping declared at `slot`:
+/// `keccak256(key ++ slot)`.
+pub fn mapping_slot(key: impl MappingKey, slot: Slot) -> Slot {
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(key.key_word().as_slice());
+    data[32..].copy_from_slice(&slot.to_be_bytes::<32>());
+
+    U256::from_be_bytes(keccak256(data).0)
+}
+
+fn mask(bytes: u8) -> U256 {
+    if bytes >= 32 {
+        U256::MAX
+    } else {
+        (U256::from(1) << (usize::from(bytes) * 8)) - U256::from(1)
+    }
+}
+
+/// Extracts the `bytes` wide field starting `offset` bytes from the least
+/// significant end of `word`.
+pub fn read_packed(word: U256, offset: u8, bytes: u8) -> U256 {
+    (word >> (usize::from(offset) * 8)) & mask(bytes)
+}
+
+/// Replaces the `bytes` wide field starting `offset` bytes from the least
+/// significant end of `word` with `value`.
+pub fn write_packed(word: U256, offset: u8, bytes: u8, value: U256) -> U256 {
+    let shift = usize::from(offset) * 8;
+    let mask = mask(bytes);
+    (word & !(mask << shift)) | ((value & mask) << shift)
+}
+
+/// Conversion between a struct member and its right-aligned field value.
+pub trait PackedField: Sized {
+    fn from_word(word: U256) -> Self;
+    fn to_word(&self) -> U256;
+}
+
+impl PackedField for bool {
+    fn from_word(word: U256) -> Self {
+        !word.is_zero()
+    }
+
+    fn to_word(&self) -> U256 {
+        U256::from(u8::from(*self))
+    }
+}
+
+impl PackedField for Address {
+    fn from_word(word: U256) -> Self {
+        Address::from_word(word.into())
+    }
+
+    fn to_word(&self) -> U256 {
+        U256::from_be_bytes(self.into_word().0)
+    }
+}
+
+impl<const N: usize> PackedField for FixedBytes<N> {
+    fn from_word(word: U256) -> Self {
+        FixedBytes::from_slice(&word.to_be_bytes::<32>()[32 - N..])
+    }
+
+    fn to_word(&self) -> U256 {
+        U256::from_be_slice(self.as_slice())
+    }
+}
+
+impl<const BITS: usize, const LIMBS: usize> PackedField for Uint<BITS, LIMBS> {
+    fn from_word(word: U256) -> Self {
+        Self::from_be_slice(&word.to_be_bytes::<32>()[32 - Self::BYTES..])
+    }
+
+    fn to_word(&self) -> U256 {
+        U256::from_be_slice(&self.to_be_bytes_vec())
+    }
+}
+
+macro_rules! impl_packed_field {
+    ($($ty:ty),*) => {$(
+        impl PackedField for $ty {
+            fn from_word(word: U256) -> Self {
+                word.to()
+            }
+
+            fn to_word(&self) -> U256 {
+                U256::from(*self)
+            }
+        }
+    )*};
+}
+
+impl_packed_field!(u8, u16, u32, u64, u128);
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{U160, address, b256, uint};
+
+    use super::*;
+    use crate::contract_bindings::gate_lock_sol::gate_lock::storage::{self, Values};
+
+    #[test]
+    fn mapping_slot_matches_solidity() {
+        assert_eq!(
+            mapping_slot(U256::ZERO, U256::ZERO),
+            uint!(0xad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5_U256)
+        );
+        assert_eq!(
+            mapping_slot(U256::from(1), U256::from(2)),
+            uint!(0xe90b7bceb6e7df5418fb78d8ee546e97c83a08bbccc01a0644d599ccd2a7c2e0_U256)
+        );
+    }
+
+    #[test]
+    fn mapping_keys_encode_as_abi_words() {
+        let address = address!("00000000000000000000000000000000deadbeef");
+        assert_eq!(
+            mapping_slot(address, U256::from(1)),
+            mapping_slot(U256::from(0xdeadbeef_u64), U256::from(1))
+        );
+        assert_eq!(
+            mapping_slot(B256::from(U256::from(7)), U256::from(3)),
+            mapping_slot(U256::from(7), U256::from(3))
+        );
+    }
+
+    #[test]
+    fn value_map_slots() {
+        assert_eq!(storage::VALUE_MAP.slot, U256::from(2));
+        assert_eq!(
+            storage::valueMap(U256::ZERO),
+            uint!(0xac33ff75c19e70fe83507db0d683fd3465c996598dc972688b7ace676c89077b_U256)
+        );
+        assert_eq!(
+            storage::valueMap(U256::from(42)),
+            uint!(0x2a41d6eb867ddcfeac667c3fe429f7b1dc4c811189b3ece5135425064920a1b7_U256)
+        );
+    }
+
+    #[test]
+    fn find_looks_up_by_label() {
+        assert_eq!(find(storage::LAYOUT, "totalLength"), Some(&storage::TOTAL_LENGTH));
+        assert_eq!(find(storage::LAYOUT, "missing"), None);
+    }
+
+    #[test]
+    fn mask_widths() {
+        assert_eq!(mask(0), U256::ZERO);
+        assert_eq!(mask(1), U256::from(0xff));
+        assert_eq!(mask(8), U256::from(u64::MAX));
+        assert_eq!(mask(31), U256::MAX >> 8);
+        assert_eq!(mask(32), U256::MAX);
+    }
+
+    #[test]
+    fn packed_fields_do_not_overlap() {
+        let word = write_packed(U256::MAX, 8, 20, U256::ZERO);
+        assert_eq!(read_packed(word, 0, 8), U256::from(u64::MAX));
+        assert_eq!(read_packed(word, 8, 20), U256::ZERO);
+        assert_eq!(read_packed(word, 28, 4), U256::from(u32::MAX));
+
+        let word = write_packed(U256::ZERO, 4, 2, U256::from(0x12345));
+        assert_eq!(word, U256::from(0x2345_0000_0000_u64));
+        assert_eq!(read_packed(word, 4, 2), U256::from(0x2345));
+    }
+
+    fn round_trip<T: PackedField>(value: &T) -> T {
+        T::from_word(value.to_word())
+    }
+
+    #[test]
+    fn packed_field_round_trips() {
+        assert!(round_trip(&true));
+        assert!(!round_trip(&false));
+
+        let address = address!("00000000000000000000000000000000deadbeef");
+        assert_eq!(address.to_word(), U256::from(0xdeadbeef_u64));
+        assert_eq!(round_trip(&address), address);
+
+        let bytes = b256!("0000000000000000000000000000000000000000000000000000000000abcdef");
+        assert_eq!(round_trip(&bytes), bytes);
+        let short = FixedBytes::<4>::from([1, 2, 3, 4]);
+        assert_eq!(short.to_word(), U256::from(0x01020304));
+        assert_eq!(round_trip(&short), short);
+
+        assert_eq!(round_trip(&U160::MAX), U160::MAX);
+        assert_eq!(round_trip(&0xab_u8), 0xab);
+        assert_eq!(round_trip(&u64::MAX), u64::MAX);
+        assert_eq!(round_trip(&u128::MAX), u128::MAX);
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let values = Values { firstValue: u64::MAX, secondValue: U160::MAX, is_unlocked: true };
+        assert_eq!(values.encode_packed(), U256::MAX >> 31);
+        assert_eq!(Values::decode_packed(values.encode_packed()), values);
+
+        let values = Values { firstValue: 7, secondValue: U160::from(9), is_unlocked: false };
+        assert_eq!(values.encode_packed(), U256::from(7) | (U256::from(9) << 64));
+        assert_eq!(Values::decode_packed(values.encode_packed()), values);
+    }
+
+    #[test]
+    fn is_unlocked_is_bit_224() {
+        let unlocked = Values { is_unlocked: true, ..Default::default() };
+        assert_eq!(unlocked.encode_packed(), U256::from(1) << 224);
+        assert!(Values::decode_packed(U256::from(1) << 224).is_unlocked);
+        assert!(!Values::decode_packed(U256::from(1) << 223).is_unlocked);
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    fmt,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use alloy::primitives::{Address, U160};
+
+use crate::{
+    Payload,
+    backend::{SharedAnvilBackend, SolverBackend},
+    environment_deployment::AnvilControls,
+    payload_generators::PayloadGenerator,
+};
+
+/// Upper bound on solver runs spent shrinking a single failure.
+pub const MAX_SHRINK_RUNS: usize = 256;
+
+/// How a stress run picks its payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StressConfig {
+    pub trials: usize,
+    pub generator: PayloadGenerator,
+    /// Seed of the first trial; trial `i` uses `seed + i`.
+    pub seed: u64,
+    pub shrink: bool,
+}
+
+impl StressConfig {
+    pub fn new(trials: usize) -> Self {
+        Self { trials, generator: PayloadGenerator::Random, seed: rand::random(), shrink: true }
+    }
+
+    pub fn with_generator(self, generator: PayloadGenerator) -> Self {
+        Self { generator, ..self }
+    }
+
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    pub fn with_shrink(self, shrink: bool) -> Self {
+        Self { shrink, ..self }
+    }
+}
+
+/// A trial the solver did not pass.
+#[derive(Debug, Clone)]
+pub struct TrialFailure {
+    pub seed: u64,
+    pub payload: Vec<Payload>,
+    /// The error the solver or the deployment returned, or `None` if the
+    /// solver returned `false`.
+    pub error: Option<String>,
+    /// Smallest payload found that still fails, when shrinking is enabled.
+    pub shrunk: Option<Vec<Payload>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StressReport {
+    pub passed: usize,
+    pub failed: usize,
+    /// Time taken by each trial, deployment included.
+    pub timings: Vec<Duration>,
+    pub failures: Vec<TrialFailure>,
+}
+
+impl StressReport {
+    pub fn total_time(&self) -> Duration {
+        self.timings.iter().sum()
+    }
+
+    pub fn mean_time(&self) -> Duration {
+        match self.timings.len() {
+            0 => Duration::ZERO,
+            n => self.total_time() / n as u32,
+        }
+    }
+
+    pub fn max_time(&self) -> Duration {
+        self.timings.iter().max().copied().unwrap_or_default()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+impl fmt::Display for StressReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} passed, {} failed in {:?} (mean {:?}, max {:?})",
+            self.passed,
+            self.failed,
+            self.total_time(),
+            self.mean_time(),
+            self.max_time()
+        )?;
+
+        for failure in &self.failures {
+            write!(f, "  seed {}: {} entries", failure.seed, failure.payload.len())?;
+            if let Some(shrunk) = &failure.shrunk {
+                write!(f, ", shrunk to {}", shrunk.len())?;
+            }
+            match &failure.error {
+                Some(error) => writeln!(f, " ({error})")?,
+                None => writeln!(f, " (returned false)")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the solver against `config.trials` generated payloads on `backend`,
+/// shrinking every failing payload to a minimal reproduction.
+pub async fn run_stress<B, F, O>(backend: &B, config: StressConfig, f: F) -> StressReport
+where
+    B: SolverBackend,
+    F: Fn(Address, B::Db) -> O,
+    O: Future<Output = eyre::Result<bool>>,
+{
+    let mut report = StressReport::default();
+
+    for trial in 0..config.trials {
+        let seed = config.seed.wrapping_add(trial as u64);
+        let payload = config.generator.generate(seed);
+
+        let start = Instant::now();
+        let outcome = attempt(backend, payload.clone(), &f).await;
+        report.timings.push(start.elapsed());
+
+        // A payload the backend cannot deploy says nothing about the solver,
+        // so there is nothing to shrink.
+        let (error, shrinkable) = match &outcome {
+            Outcome::Solved => {
+                report.passed += 1;
+                continue;
+            }
+            Outcome::ReturnedFalse => (None, true),
+            Outcome::SolverError(e) => (Some(format!("{e:#}")), true),
+            Outcome::DeployError(e) => (Some(format!("deploy failed: {e:#}")), false),
+        };
+
+        println!("Trial {trial} ({} seed {seed}) failed", config.generator.name());
+        let shrunk = if config.shrink && shrinkable {
+            Some(shrink(backend, payload.clone(), &outcome, &f).await)
+        } else {
+            None
+        };
+
+        report.failed += 1;
+        report
+            .failures
+            .push(TrialFailure { seed, payload, error, shrunk });
+    }
+
+    report
+}
+
+/// Same as [`run_stress`] on a single anvil instance shared by every trial,
+/// mirroring [`deploy_setup_with_solver`](crate::deploy_setup_with_solver).
+pub async fn stress_with_solver<F, O>(config: StressConfig, f: F) -> eyre::Result<StressReport>
+where
+    F: Fn(Address, Arc<AnvilControls>) -> O,
+    O: Future<Output = eyre::Result<bool>>,
+{
+    let backend = SharedAnvilBackend::spawn().await?;
+    let report = run_stress(&backend, config, f).await;
+    print!("{report}");
+
+    Ok(report)
+}
+
+/// How a single solver run ended.
+#[derive(Debug)]
+pub enum Outcome {
+    Solved,
+    ReturnedFalse,
+    SolverError(eyre::Report),
+    DeployError(eyre::Report),
+}
+
+impl Outcome {
+    /// Whether both runs are solver failures of the same kind. Deploy errors
+    /// never match, so shrinking cannot trade a solver failure for a payload
+    /// the backend rejects.
+    fn fails_like(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ReturnedFalse, Self::ReturnedFalse)
+                | (Self::SolverError(_), Self::SolverError(_))
+        )
+    }
+}
+
+/// Like [`run_on_backend`](crate::backend::run_on_backend), keeping deploy
+/// errors apart from solver errors.
+async fn attempt<B, F, O>(backend: &B, payload: Vec<Payload>, f: &F) -> Outcome
+where
+    B: SolverBackend,
+    F: Fn(Address, B::Db) -> O,
+    O: Future<Output = eyre::Result<bool>>,
+{
+    let (address, db) = match backend.deploy(payload).await {
+        Ok(deployed) => deployed,
+        Err(e) => return Outcome::DeployError(e),
+    };
+
+    match f(address, db).await {
+        Ok(true) => Outcome::Solved,
+        Ok(false) => Outcome::ReturnedFalse,
+        Err(e) => Outcome::SolverError(e),
+    }
+}
+
+/// Greedily removes chunks of entries, then simplifies the values of the
+/// remaining ones, keeping every change under which the solver still fails
+/// the same way it failed on `payload`, as reported by `original`: returning
+/// `false` or returning an error. Returns `payload` unchanged if `original` is
+/// not a solver failure.
+pub async fn shrink<B, F, O>(
+    backend: &B,
+    payload: Vec<Payload>,
+    original: &Outcome,
+    f: &F,
+) -> Vec<Payload>
+where
+    B: SolverBackend,
+    F: Fn(Address, B::Db) -> O,
+    O: Future<Output = eyre::Result<bool>>,
+{
+    if matches!(original, Outcome::Solved | Outcome::DeployError(_)) {
+        return payload;
+    }
+
+    let mut current = payload;
+    let mut runs = 0;
+
+    let mut chunk = current.len().div_ceil(2);
+    while chunk > 0 && runs < MAX_SHRINK_RUNS {
+        let mut start = 0;
+        while start < current.len() && runs < MAX_SHRINK_RUNS {
+            let end = (start + chunk).min(current.len());
+            let mut candidate = current.clone();
+            candidate.drain(start..end);
+
+            runs += 1;
+            if attempt(backend, candidate.clone(), f)
+                .await
+                .fails_like(original)
+            {
+                current = candidate;
+            } else {
+                start += chunk;
+            }
+        }
+        chunk /= 2;
+    }
+
+    for i in 0..current.len() {
+        let entry = &current[i];
+        let simpler = [
+            Payload { firstValue: entry.firstValue % 2, ..entry.clone() },
+            Payload { secondValue: U160::ZERO, ..entry.clone() },
+        ];
+
+        for simpler in simpler {
+            if runs >= MAX_SHRINK_RUNS || simpler == current[i] {
+                continue;
+            }
+
+            let mut candidate = current.clone();
+            candidate[i] = simpler;
+
+            runs += 1;
+            if attempt(backend, candidate.clone(), f)
+                .await
+                .fails_like(original)
+            {
+                current = candidate;
+            }
+        }
+    }
+
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::U256;
+    use revm::DatabaseRef;
+
+    use super::*;
+    use crate::{
+        backend::InMemoryBackend,
+        contract_bindings::gate_lock_sol::gate_lock::storage::{self, Values},
+        in_memory::InMemoryDb,
+    };
+
+    const BAD: u64 = 0xbad;
+
+    /// Walks the chain the constructor wrote and fails on the entry whose
+    /// `secondValue` is [`BAD`].
+    async fn fails_on_bad(address: Address, db: InMemoryDb) -> eyre::Result<bool> {
+        let length = db.storage_ref(address, storage::TOTAL_LENGTH.slot)?;
+        let mut slot = U256::ZERO;
+        for _ in 0..length.to::<usize>() {
+            let values = Values::decode_packed(db.storage_ref(address, storage::valueMap(slot))?);
+            if values.secondValue == U160::from(BAD) {
+                return Ok(false);
+            }
+            slot = if values.firstValue % 2 == 0 {
+                U256::from(values.firstValue)
+            } else {
+                U256::from(values.secondValue)
+            };
+        }
+
+        Ok(true)
+    }
+
+    #[tokio::test]
+    async fn shrinks_to_the_failing_entry() {
+        let payload = vec![
+            Payload { firstValue: 100, secondValue: U160::from(0x123) },
+            Payload { firstValue: 201, secondValue: U160::from(0x456) },
+            Payload { firstValue: 7, secondValue: U160::from(BAD) },
+            Payload { firstValue: 88, secondValue: U160::from(0x789) },
+            Payload { firstValue: 4, secondValue: U160::from(1) },
+        ];
+
+        let outcome = attempt(&InMemoryBackend, payload.clone(), &fails_on_bad).await;
+        assert!(matches!(outcome, Outcome::ReturnedFalse));
+
+        let shrunk = shrink(&InMemoryBackend, payload, &outcome, &fails_on_bad).await;
+        assert_eq!(shrunk, vec![Payload { firstValue: 1, secondValue: U160::from(BAD) }]);
+    }
+
+    #[tokio::test]
+    async fn does_not_shrink_a_solved_payload() {
+        let payload = vec![Payload { firstValue: 2, secondValue: U160::from(3) }];
+        let shrunk =
+            shrink(&InMemoryBackend, payload.clone(), &Outcome::Solved, &fails_on_bad).await;
+        assert_eq!(shrunk, payload);
+    }
+}